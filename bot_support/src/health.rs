@@ -0,0 +1,217 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Requests larger than this (request line plus headers) are cut off.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// Counters and last-known state exposed on /healthz and /metrics.
+pub struct Metrics {
+    prefix: &'static str,
+    stale_after: Duration,
+    polls: AtomicU64,
+    posts: AtomicU64,
+    media_uploads: AtomicU64,
+    failures: AtomicU64,
+    rate_limit_waits: AtomicU64,
+    queue_depth: AtomicU64,
+    last_success: AtomicU64,
+    last_error: Mutex<Option<(u64, String)>>,
+}
+
+impl Metrics {
+    /// `prefix` namespaces the Prometheus metrics; `stale_after` is how long without a
+    /// successful poll before /healthz reports unhealthy.
+    pub fn new(prefix: &'static str, stale_after: Duration) -> Arc<Self> {
+        Arc::new(Metrics {
+            prefix,
+            stale_after,
+            polls: AtomicU64::new(0),
+            posts: AtomicU64::new(0),
+            media_uploads: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            rate_limit_waits: AtomicU64::new(0),
+            queue_depth: AtomicU64::new(0),
+            last_success: AtomicU64::new(now()),
+            last_error: Mutex::new(None),
+        })
+    }
+
+    pub fn poll_ok(&self) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.last_success.store(now(), Ordering::Relaxed);
+    }
+
+    pub fn poll_failed(&self, error: impl ToString) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.failure(error);
+    }
+
+    pub fn post_ok(&self) {
+        self.posts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn media_uploaded(&self) {
+        self.media_uploads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failure(&self, error: impl ToString) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = Some((now(), error.to_string()));
+        }
+    }
+
+    pub fn rate_limited(&self) {
+        self.rate_limit_waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.store(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Renders the /healthz body and whether the bot counts as healthy.
+    fn health(&self) -> (bool, String) {
+        let last_success = self.last_success.load(Ordering::Relaxed);
+        let healthy = now().saturating_sub(last_success) <= self.stale_after.as_secs();
+        let last_error = self.last_error.lock().ok().and_then(|e| e.clone());
+        let body = serde_json::json!({
+            "status": if healthy { "ok" } else { "stale" },
+            "last_success": last_success,
+            "last_error": last_error.as_ref().map(|(_, e)| e),
+            "last_error_at": last_error.as_ref().map(|(t, _)| t),
+        });
+        (healthy, body.to_string())
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    fn prometheus(&self) -> String {
+        let p = self.prefix;
+        let mut out = String::new();
+        for (name, kind, help, value) in [
+            ("polls_total", "counter", "Polling cycles run", &self.polls),
+            ("posts_total", "counter", "Items published successfully", &self.posts),
+            ("media_uploads_total", "counter", "Media files uploaded", &self.media_uploads),
+            ("failures_total", "counter", "Failed requests", &self.failures),
+            ("rate_limit_waits_total", "counter", "Times we backed off after HTTP 429", &self.rate_limit_waits),
+            ("queue_depth", "gauge", "Items waiting to be published", &self.queue_depth),
+            ("last_success_timestamp_seconds", "gauge", "Unix time of the last successful poll", &self.last_success),
+        ] {
+            out.push_str(&format!("# HELP {p}_{name} {help}\n# TYPE {p}_{name} {kind}\n{p}_{name} {}\n", value.load(Ordering::Relaxed)));
+        }
+        out
+    }
+}
+
+/// Serves /healthz and /metrics on `addr` until the process exits.
+pub async fn serve(addr: String, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&addr).await?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                let _ = respond(stream, &metrics).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Answers a single HTTP/1.x request; only the request line is looked at, but the headers
+/// are read up to the blank line so the client isn't reset when the connection closes.
+async fn respond<S: AsyncRead + AsyncWrite + Unpin>(stream: S, metrics: &Metrics) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES));
+    let mut request_line = Vec::new();
+    reader.read_until(b'\n', &mut request_line).await?;
+    let mut header = Vec::new();
+    loop {
+        header.clear();
+        let n = reader.read_until(b'\n', &mut header).await?;
+        if n == 0 || header == b"\r\n" || header == b"\n" {
+            break;
+        }
+    }
+    let mut stream = reader.into_inner().into_inner();
+    let request_line = String::from_utf8_lossy(&request_line);
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = match path {
+        "/healthz" => {
+            let (healthy, body) = metrics.health();
+            (if healthy { "200 OK" } else { "503 Service Unavailable" }, "application/json", body)
+        }
+        "/metrics" => ("200 OK", "text/plain; version=0.0.4", metrics.prometheus()),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    /// Sends `request` to the endpoint and returns the raw response.
+    async fn request(metrics: &Metrics, request: &[u8]) -> String {
+        let (mut client, server) = duplex(64 * 1024);
+        client.write_all(request).await.unwrap();
+        respond(server, metrics).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn healthz_reports_ok_after_a_poll() {
+        let metrics = Metrics::new("test", Duration::from_secs(60));
+        metrics.poll_ok();
+        let response = request(&metrics, b"GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains(r#""status":"ok""#));
+    }
+
+    #[tokio::test]
+    async fn healthz_goes_stale() {
+        let metrics = Metrics::new("test", Duration::from_secs(0));
+        metrics.last_success.store(now() - 10, Ordering::Relaxed);
+        metrics.failure("boom");
+        let response = request(&metrics, b"GET /healthz HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{}", response);
+        assert!(response.contains(r#""last_error":"boom""#));
+    }
+
+    #[tokio::test]
+    async fn metrics_are_prefixed_counters() {
+        let metrics = Metrics::new("mirror", Duration::from_secs(60));
+        metrics.post_ok();
+        metrics.post_ok();
+        metrics.set_queue_depth(4);
+        let response = request(&metrics, b"GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.contains("# TYPE mirror_posts_total counter\nmirror_posts_total 2\n"), "{}", response);
+        assert!(response.contains("mirror_queue_depth 4\n"));
+    }
+
+    #[tokio::test]
+    async fn large_headers_are_read_past() {
+        let metrics = Metrics::new("test", Duration::from_secs(60));
+        let mut raw = b"GET /nope HTTP/1.1\r\n".to_vec();
+        for i in 0..100 {
+            raw.extend_from_slice(format!("X-Header-{}: {}\r\n", i, "x".repeat(40)).as_bytes());
+        }
+        raw.extend_from_slice(b"\r\n");
+        let response = request(&metrics, &raw).await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", response);
+    }
+}
@@ -0,0 +1,10 @@
+//! Plumbing every bot in this repository shares: `tracing` setup from the environment and,
+//! behind features, the /healthz + /metrics endpoint (`health`) and crash-safe JSON state
+//! files (`store`).
+
+pub mod logging;
+
+#[cfg(feature = "health")]
+pub mod health;
+#[cfg(feature = "store")]
+pub mod store;
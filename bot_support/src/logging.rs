@@ -0,0 +1,244 @@
+use serde_json::{Map, Value};
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::format::{DefaultFields, DefaultVisitor, Writer};
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Sets up `tracing` from the environment. Call after loading `.env`.
+///
+/// - `LOG_LEVEL` (or `RUST_LOG`): filter directive, e.g. `debug` or `warn`. Defaults to `info`.
+/// - `LOG_FORMAT`: `text` (default) or `json` for journald/Loki ingestion.
+/// - `LOG_EMOJI`: set to `false` to strip emoji from messages and field values.
+pub fn init() {
+    let filter = EnvFilter::try_from_env("LOG_LEVEL")
+        .or_else(|_| EnvFilter::try_from_default_env())
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let json = env::var("LOG_FORMAT").map(|v| v.eq_ignore_ascii_case("json")).unwrap_or(false);
+    let strip_emoji = env::var("LOG_EMOJI").map(|v| v == "false").unwrap_or(false);
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(!json && io::stdout().is_terminal());
+
+    // Emoji are removed from the recorded values before formatting, so escaping and
+    // quoting in the output stay intact
+    match (json, strip_emoji) {
+        (true, true) => builder.event_format(JsonWithoutEmoji).init(),
+        (true, false) => builder.json().init(),
+        (false, true) => builder.fmt_fields(FieldsWithoutEmoji).init(),
+        (false, false) => builder.init(),
+    }
+}
+
+/// The default text field formatter, minus emoji.
+struct FieldsWithoutEmoji;
+
+impl<'a> MakeVisitor<Writer<'a>> for FieldsWithoutEmoji {
+    type Visitor = StripEmoji<DefaultVisitor<'a>>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        StripEmoji(DefaultFields::new().make_visitor(target))
+    }
+}
+
+/// Passes every recorded value on to the wrapped visitor with its emoji removed.
+struct StripEmoji<V>(V);
+
+impl<V: Visit> Visit for StripEmoji<V> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.record_str(field, &strip_emoji(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let text = strip_emoji(&format!("{:?}", value));
+        self.0.record_debug(field, &format_args!("{}", text));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+}
+
+impl<V: VisitOutput<fmt::Result>> VisitOutput<fmt::Result> for StripEmoji<V> {
+    fn finish(self) -> fmt::Result {
+        self.0.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for StripEmoji<V> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.0.writer()
+    }
+}
+
+/// One JSON object per event, shaped like `tracing_subscriber`'s own JSON output
+/// (`timestamp`, `level`, `fields`, `target`), with emoji removed from the field values.
+struct JsonWithoutEmoji;
+
+impl<S, N> FormatEvent<S, N> for JsonWithoutEmoji
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+        let mut fields = JsonFields(Map::new());
+        event.record(&mut fields);
+
+        let mut line = Map::new();
+        line.insert("timestamp".into(), timestamp.into());
+        line.insert("level".into(), event.metadata().level().as_str().into());
+        line.insert("fields".into(), Value::Object(fields.0));
+        line.insert("target".into(), event.metadata().target().into());
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Collects event fields as JSON values, stripping emoji from text.
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), strip_emoji(value).into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().into(), strip_emoji(&format!("{:?}", value)).into());
+    }
+}
+
+/// Removes emoji along with the spacing they leave behind.
+fn strip_emoji(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut stripped = false;
+    for c in s.chars() {
+        if is_emoji(c) {
+            stripped = true;
+            continue;
+        }
+        if stripped {
+            if c == ' ' && (out.is_empty() || out.ends_with(' ')) {
+                continue;
+            }
+            if c == '\n' && out.ends_with(' ') {
+                out.pop();
+            }
+            stripped = false;
+        }
+        out.push(c);
+    }
+    if stripped && out.ends_with(' ') {
+        out.pop();
+    }
+    out
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1F000}'..='\u{1FAFF}' // pictographs, emoticons, transport, flags
+        | '\u{2300}'..='\u{23FF}' // technical symbols (⌛, ⏰)
+        | '\u{2600}'..='\u{27BF}' // misc symbols and dingbats (☕, ✨, ✅)
+        | '\u{2B00}'..='\u{2BFF}' // stars and arrows (⭐)
+        | '\u{FE0F}' | '\u{200D}' | '\u{20E3}' // variation selector, joiner, keycap
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_emoji_and_their_space_are_removed() {
+        assert_eq!(strip_emoji("🚀 Mirror started"), "Mirror started");
+        assert_eq!(strip_emoji("⚠️ Couldn't upload"), "Couldn't upload");
+        assert_eq!(strip_emoji("saved ✅"), "saved");
+        assert_eq!(strip_emoji("a 👨‍👩‍👧 b"), "a b");
+    }
+
+    #[test]
+    fn plain_text_is_untouched() {
+        assert_eq!(strip_emoji("quote \" and → arrow, ünïcode"), "quote \" and → arrow, ünïcode");
+    }
+
+    /// Runs `log` under a subscriber built by `setup` and returns what it printed.
+    fn capture<F>(setup: F, log: impl FnOnce()) -> String
+    where
+        F: FnOnce(tracing_subscriber::fmt::SubscriberBuilder<DefaultFields, tracing_subscriber::fmt::format::Format, EnvFilter, Buffer>) -> Box<dyn Subscriber + Send + Sync>,
+    {
+        let buffer = Buffer::default();
+        let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new("info")).with_ansi(false).with_writer(buffer.clone());
+        tracing::subscriber::with_default(setup(builder), log);
+        let out = buffer.0.lock().unwrap().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn json_lines_stay_valid_json() {
+        let out = capture(
+            |b| Box::new(b.event_format(JsonWithoutEmoji).finish()),
+            || tracing::info!(file = "a \"✨\" b.json", count = 3, error = %"🌩️ timeout", "✅ Saved \"{}\"", "x"),
+        );
+        let line: Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "Saved \"x\"");
+        assert_eq!(line["fields"]["file"], "a \"\" b.json");
+        assert_eq!(line["fields"]["count"], 3);
+        assert_eq!(line["fields"]["error"], "timeout");
+    }
+
+    #[test]
+    fn text_lines_keep_their_quoting() {
+        let out = capture(
+            |b| Box::new(b.fmt_fields(FieldsWithoutEmoji).finish()),
+            || tracing::warn!(file = "✨ queue.json", error = %"🌩️ timeout", "⚠️ Couldn't save"),
+        );
+        assert!(out.contains(r#"Couldn't save file="queue.json" error=timeout"#), "{}", out);
+        assert!(!out.contains('✨'));
+    }
+}
@@ -0,0 +1,71 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Reads a JSON state file, or `None` if it doesn't exist yet.
+///
+/// A file that exists but can't be parsed is an error rather than an empty state: starting
+/// over would repeat everything the file was meant to remember.
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> io::Result<Option<T>> {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_reader(BufReader::new(file))
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} is corrupt: {}", path.display(), e)))
+}
+
+/// Writes a JSON state file atomically: the data goes to a temporary file next to it, which
+/// then replaces the old one, so a crash mid-write never leaves a truncated file behind.
+pub fn save<T: Serialize + ?Sized>(path: impl AsRef<Path>, value: &T) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("bot-support-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn missing_file_is_none() {
+        let loaded: Option<HashMap<String, String>> = load(temp_path("missing.json")).unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = temp_path("round_trip.json");
+        let value = HashMap::from([("a".to_string(), "1".to_string())]);
+        save(&path, &value).unwrap();
+        assert_eq!(load::<HashMap<String, String>>(&path).unwrap(), Some(value));
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn corrupt_file_is_an_error() {
+        let path = temp_path("corrupt.json");
+        fs::write(&path, "{\"a\": ").unwrap();
+        let err = load::<HashMap<String, String>>(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
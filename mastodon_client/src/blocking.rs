@@ -0,0 +1,98 @@
+//! Synchronous version of [`crate::Client`] for tools that run once and exit.
+//!
+//! Each call runs the async client on a private single-threaded runtime, so this client
+//! must not be used from inside another tokio runtime.
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{multipart, Account, Attachment, Context, Conversation, Instance, NewStatus, Posted, Status};
+
+/// Blocking client for one account on one Mastodon server.
+pub struct Client {
+    inner: crate::Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// Forwards each listed method to the async client and waits for it.
+macro_rules! blocking {
+    ($( $(#[$doc:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty; )*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret> {
+                self.runtime.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `https://mastodon.social`.
+    pub fn new(base_url: &str, token: &str) -> Result<Self> {
+        Self::with_http(reqwest::Client::new(), base_url, token)
+    }
+
+    /// Uses a preconfigured HTTP client (timeouts, user agent).
+    pub fn with_http(http: reqwest::Client, base_url: &str, token: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        Ok(Client { inner: crate::Client::with_http(http, base_url, token), runtime })
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    /// The server's host name, e.g. `mastodon.social`.
+    pub fn domain(&self) -> &str {
+        self.inner.domain()
+    }
+
+    blocking! {
+        /// GET `path` and parse the JSON body.
+        fn get(&self, path: &str) -> Value;
+        /// GETs a paginated list endpoint, following `Link` headers for up to `max_pages` pages.
+        fn get_all(&self, path: &str, max_pages: usize) -> Vec<Value>;
+        /// POST a JSON body to `path` and parse the JSON response.
+        fn post(&self, path: &str, body: &Value) -> Value;
+        /// DELETE `path`.
+        fn delete(&self, path: &str) -> ();
+        /// Downloads any file without credentials.
+        fn download(&self, url: &str) -> Vec<u8>;
+        /// The account the token belongs to.
+        fn verify_credentials(&self) -> Account;
+        /// Updates the token's own profile from a multipart form.
+        fn update_credentials(&self, form: multipart::Form) -> Account;
+        /// Every follower of an account, across all pages.
+        fn followers(&self, account_id: &str) -> Vec<Account>;
+        /// One page of an account's statuses, newest first.
+        fn account_statuses(&self, account_id: &str, query: &[(&str, &str)]) -> Vec<Status>;
+        fn status(&self, id: &str) -> Status;
+        fn context(&self, id: &str) -> Context;
+        fn post_status(&self, status: &NewStatus) -> Status;
+        /// Posts a status that may carry `scheduled_at`.
+        fn post_scheduled_status(&self, status: &NewStatus) -> Posted;
+        fn delete_status(&self, id: &str) -> ();
+        /// Boosts a status; `visibility` can restrict the boost.
+        fn reblog(&self, id: &str, visibility: Option<&str>) -> Status;
+        fn unreblog(&self, id: &str) -> Status;
+        fn favourite(&self, id: &str) -> Status;
+        fn unfavourite(&self, id: &str) -> Status;
+        fn bookmark(&self, id: &str) -> Status;
+        fn unbookmark(&self, id: &str) -> Status;
+        fn pin(&self, id: &str) -> Status;
+        fn unpin(&self, id: &str) -> Status;
+        /// The token's bookmarks, newest first, for up to `max_pages` pages.
+        fn bookmarks(&self, max_pages: usize) -> Vec<Status>;
+        /// The token's favourites, newest first, for up to `max_pages` pages.
+        fn favourites(&self, max_pages: usize) -> Vec<Status>;
+        /// The newest direct message threads.
+        fn conversations(&self) -> Vec<Conversation>;
+        /// Removes a conversation from the list; the statuses themselves stay.
+        fn remove_conversation(&self, id: &str) -> ();
+        fn instance(&self) -> Instance;
+        /// Resolves a status URL (possibly on another server) through search.
+        fn resolve_status(&self, url: &str) -> Option<Status>;
+        /// Uploads a file and waits until the server has processed it.
+        fn upload_media(&self, bytes: Vec<u8>, filename: &str, mime: &str, description: Option<&str>) -> Attachment;
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A Mastodon account (`/api/v1/accounts/:id`, `verify_credentials`, followers…).
+///
+/// Fields the bots don't read are kept in `extra`, so an account serializes back to the
+/// JSON the server sent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Account {
+    pub id: String,
+    #[serde(default)]
+    pub username: String,
+    /// `user` for local accounts, `user@domain` for remote ones
+    #[serde(default)]
+    pub acct: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub header: String,
+    /// Bio as HTML
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    /// Custom emoji used in the display name and bio
+    #[serde(default)]
+    pub emojis: Vec<Emoji>,
+    /// Plain-text bio and fields, only present for the token's own account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<AccountSource>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A profile metadata field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+}
+
+/// The editable source of the token's own profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSource {
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+/// A status, as returned by timelines, `/api/v1/statuses/:id` and search.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub id: String,
+    /// ActivityPub ID, stable across servers
+    #[serde(default)]
+    pub uri: String,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub created_at: String,
+    /// When the status was last edited, if ever
+    #[serde(default)]
+    pub edited_at: Option<String>,
+    pub account: Account,
+    /// Content as HTML
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub spoiler_text: String,
+    #[serde(default)]
+    pub visibility: String,
+    pub in_reply_to_id: Option<String>,
+    #[serde(default)]
+    pub media_attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// Custom emoji used in the content, warning and poll
+    #[serde(default)]
+    pub emojis: Vec<Emoji>,
+    #[serde(default)]
+    pub poll: Option<Poll>,
+    /// Link preview
+    #[serde(default)]
+    pub card: Option<Card>,
+    /// The boosted status, if this is a boost
+    #[serde(default)]
+    pub reblog: Option<Box<Status>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A media attachment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    /// `image`, `video`, `gifv`, `audio` or `unknown`
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// `None` while the server is still processing the file
+    pub url: Option<String>,
+    pub preview_url: Option<String>,
+    /// The original file on the author's server, for remote posts
+    pub remote_url: Option<String>,
+    /// Alt text
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A hashtag used in a status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    /// Without the `#`
+    pub name: String,
+    #[serde(default)]
+    pub url: String,
+}
+
+/// A custom emoji, written `:shortcode:` in text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emoji {
+    pub shortcode: String,
+    pub url: String,
+    #[serde(default)]
+    pub static_url: String,
+}
+
+/// A poll attached to a status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Poll {
+    #[serde(default)]
+    pub id: String,
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub expired: bool,
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub votes_count: u64,
+    /// Distinct voters; `None` for single-choice polls on some servers
+    pub voters_count: Option<u64>,
+    #[serde(default)]
+    pub options: Vec<PollOption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollOption {
+    pub title: String,
+    /// `None` while the results are hidden
+    pub votes_count: Option<u64>,
+}
+
+/// The preview card of the first link in a status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub image: Option<String>,
+}
+
+/// A direct message thread (`/api/v1/conversations`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    pub last_status: Option<Status>,
+}
+
+/// Server information (`/api/v2/instance`), limited to what the bots read.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Instance {
+    pub domain: String,
+    #[serde(default)]
+    pub configuration: Configuration,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Configuration {
+    #[serde(default)]
+    pub statuses: StatusLimits,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusLimits {
+    pub max_characters: usize,
+}
+
+impl Default for StatusLimits {
+    /// Mastodon's stock limit, for servers that don't report one
+    fn default() -> Self {
+        StatusLimits { max_characters: 500 }
+    }
+}
+
+/// The statuses around a status (`/api/v1/statuses/:id/context`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Context {
+    pub ancestors: Vec<Status>,
+    pub descendants: Vec<Status>,
+}
+
+/// A status waiting to be published at `scheduled_at`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledStatus {
+    pub id: String,
+    pub scheduled_at: String,
+}
+
+/// What the server made of a new status: published right away, or scheduled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Posted {
+    Published(Box<Status>),
+    Scheduled(ScheduledStatus),
+}
+
+/// Parameters for `POST /api/v1/statuses`; unset fields use the server's defaults.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NewStatus {
+    pub status: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub media_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spoiler_text: Option<String>,
+    /// RFC 3339 time to publish at (at least 5 minutes ahead)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_at: Option<String>,
+}
+
+impl NewStatus {
+    pub fn new(text: impl Into<String>) -> Self {
+        NewStatus { status: text.into(), ..Default::default() }
+    }
+
+    pub fn visibility(mut self, visibility: impl Into<String>) -> Self {
+        self.visibility = Some(visibility.into());
+        self
+    }
+
+    pub fn media(mut self, media_ids: Vec<String>) -> Self {
+        self.media_ids = media_ids;
+        self
+    }
+
+    pub fn in_reply_to(mut self, id: impl Into<String>) -> Self {
+        self.in_reply_to_id = Some(id.into());
+        self
+    }
+
+    /// Puts the text behind a content warning; an empty warning is left out.
+    pub fn spoiler(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        self.spoiler_text = (!text.is_empty()).then_some(text);
+        self
+    }
+
+    pub fn scheduled_at(mut self, at: impl Into<String>) -> Self {
+        self.scheduled_at = Some(at.into());
+        self
+    }
+}
@@ -0,0 +1,460 @@
+//! Mastodon API client shared by the bots in this repository.
+//!
+//! [`Client`] is async (tokio); with the `blocking` feature, [`blocking::Client`] offers the
+//! same calls for run-once tools. Typed methods cover what the bots use (accounts, statuses,
+//! media, search, bookmarks, favourites, conversations); `get`/`get_all`/`post`/`delete` reach any other endpoint as raw
+//! JSON. Failed requests are [`ApiError`]s, so callers can react to 404s and rate limits.
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod entities;
+
+pub use entities::*;
+pub use reqwest::multipart;
+
+use anyhow::{Context as _, Result};
+use regex::Regex;
+use reqwest::header::LINK;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Longest response body kept in an [`ApiError`].
+const MAX_ERROR_BODY: usize = 500;
+/// How long `upload_media` waits for the server to process a file.
+const MEDIA_POLLS: usize = 30;
+
+/// Authenticated client for one account on one Mastodon server.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+/// A non-2xx answer from the server.
+#[derive(Debug)]
+pub struct ApiError {
+    pub url: String,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} returned {}", self.url, self.status)?;
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// The HTTP status of a failed request, or `None` for network and parsing errors.
+pub fn status_of(err: &anyhow::Error) -> Option<StatusCode> {
+    err.downcast_ref::<ApiError>().map(|e| e.status)
+}
+
+/// True if the server answered HTTP 429.
+pub fn is_rate_limited(err: &anyhow::Error) -> bool {
+    status_of(err) == Some(StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// True if the request never got an answer (connection failure or timeout), so it may be
+/// worth retrying. Errors reading a successful response are not network errors.
+pub fn is_network(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request())
+}
+
+/// True if the server answered 404 or 410, i.e. the thing is gone.
+pub fn is_gone(err: &anyhow::Error) -> bool {
+    matches!(status_of(err), Some(StatusCode::NOT_FOUND | StatusCode::GONE))
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `https://mastodon.social`.
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Self::with_http(reqwest::Client::new(), base_url, token)
+    }
+
+    /// Uses a preconfigured HTTP client (timeouts, user agent).
+    pub fn with_http(http: reqwest::Client, base_url: &str, token: &str) -> Self {
+        Client {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The server's host name, e.g. `mastodon.social`, for turning local `acct`s into full handles.
+    pub fn domain(&self) -> &str {
+        let rest = self.base_url.split("://").nth(1).unwrap_or(&self.base_url);
+        rest.split('/').next().unwrap_or(rest)
+    }
+
+    // --- Raw JSON ---
+
+    /// GET `path` (e.g. `/api/v1/bookmarks?limit=20`) and parse the JSON body.
+    pub async fn get(&self, path: &str) -> Result<Value> {
+        self.fetch(path).await
+    }
+
+    /// GETs a paginated list endpoint, following `Link: rel="next"` headers for up to `max_pages` pages.
+    pub async fn get_all(&self, path: &str, max_pages: usize) -> Result<Vec<Value>> {
+        self.fetch_all(path, max_pages).await
+    }
+
+    /// POST a JSON body to `path` and parse the JSON response.
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        let url = self.url(path);
+        let resp = self.send(self.http.post(&url).json(body), &url).await?;
+        Ok(resp.json().await.unwrap_or(Value::Null))
+    }
+
+    /// DELETE `path`.
+    pub async fn delete(&self, path: &str) -> Result<()> {
+        let url = self.url(path);
+        self.send(self.http.delete(&url), &url).await?;
+        Ok(())
+    }
+
+    /// Fetches an ActivityPub object (e.g. a Note or an actor) straight from its origin server.
+    pub async fn fetch_activitypub(&self, url: &str) -> Result<Value> {
+        let resp = self.http.get(url)
+            .header("Accept", r#"application/activity+json, application/ld+json; profile="https://www.w3.org/ns/activitystreams""#)
+            .send().await
+            .with_context(|| format!("Couldn't reach {}", url))?;
+        Ok(check(resp, url).await?.json().await?)
+    }
+
+    /// Downloads any file (media, avatars) without credentials.
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let resp = self.http.get(url).send().await.with_context(|| format!("Couldn't reach {}", url))?;
+        Ok(check(resp, url).await?.bytes().await?.to_vec())
+    }
+
+    // --- Accounts ---
+
+    /// The account the token belongs to.
+    pub async fn verify_credentials(&self) -> Result<Account> {
+        self.fetch("/api/v1/accounts/verify_credentials").await
+    }
+
+    /// Updates the token's own profile from a multipart form (`display_name`, `note`,
+    /// `avatar`, `header`, `fields_attributes[i][name]`…).
+    pub async fn update_credentials(&self, form: multipart::Form) -> Result<Account> {
+        let url = self.url("/api/v1/accounts/update_credentials");
+        Ok(self.send(self.http.patch(&url).multipart(form), &url).await?.json().await?)
+    }
+
+    /// Every follower of an account, across all pages.
+    pub async fn followers(&self, account_id: &str) -> Result<Vec<Account>> {
+        self.fetch_all(&format!("/api/v1/accounts/{}/followers?limit=80", account_id), usize::MAX).await
+    }
+
+    /// One page of an account's statuses, newest first. `query` takes the usual filters,
+    /// e.g. `[("since_id", id)]`, `[("pinned", "true")]` or `[("limit", "40"), ("max_id", id)]`.
+    pub async fn account_statuses(&self, account_id: &str, query: &[(&str, &str)]) -> Result<Vec<Status>> {
+        let url = self.url(&format!("/api/v1/accounts/{}/statuses", account_id));
+        Ok(self.send(self.http.get(&url).query(query), &url).await?.json().await?)
+    }
+
+    // --- Statuses ---
+
+    pub async fn status(&self, id: &str) -> Result<Status> {
+        self.fetch(&format!("/api/v1/statuses/{}", id)).await
+    }
+
+    pub async fn context(&self, id: &str) -> Result<Context> {
+        self.fetch(&format!("/api/v1/statuses/{}/context", id)).await
+    }
+
+    pub async fn post_status(&self, status: &NewStatus) -> Result<Status> {
+        let url = self.url("/api/v1/statuses");
+        Ok(self.send(self.http.post(&url).json(status), &url).await?.json().await?)
+    }
+
+    /// Posts a status that may carry `scheduled_at`. Servers without scheduling publish it
+    /// right away, which shows up as [`Posted::Published`].
+    pub async fn post_scheduled_status(&self, status: &NewStatus) -> Result<Posted> {
+        let url = self.url("/api/v1/statuses");
+        Ok(self.send(self.http.post(&url).json(status), &url).await?.json().await?)
+    }
+
+    pub async fn delete_status(&self, id: &str) -> Result<()> {
+        self.delete(&format!("/api/v1/statuses/{}", id)).await
+    }
+
+    /// Boosts a status; `visibility` can restrict the boost (e.g. `private`).
+    pub async fn reblog(&self, id: &str, visibility: Option<&str>) -> Result<Status> {
+        let body = match visibility {
+            Some(v) => serde_json::json!({ "visibility": v }),
+            None => serde_json::json!({}),
+        };
+        let url = self.url(&format!("/api/v1/statuses/{}/reblog", id));
+        Ok(self.send(self.http.post(&url).json(&body), &url).await?.json().await?)
+    }
+
+    pub async fn unreblog(&self, id: &str) -> Result<Status> {
+        self.action(id, "unreblog").await
+    }
+
+    pub async fn favourite(&self, id: &str) -> Result<Status> {
+        self.action(id, "favourite").await
+    }
+
+    pub async fn unfavourite(&self, id: &str) -> Result<Status> {
+        self.action(id, "unfavourite").await
+    }
+
+    pub async fn bookmark(&self, id: &str) -> Result<Status> {
+        self.action(id, "bookmark").await
+    }
+
+    pub async fn unbookmark(&self, id: &str) -> Result<Status> {
+        self.action(id, "unbookmark").await
+    }
+
+    pub async fn pin(&self, id: &str) -> Result<Status> {
+        self.action(id, "pin").await
+    }
+
+    pub async fn unpin(&self, id: &str) -> Result<Status> {
+        self.action(id, "unpin").await
+    }
+
+    /// The token's bookmarks, newest first, for up to `max_pages` pages.
+    pub async fn bookmarks(&self, max_pages: usize) -> Result<Vec<Status>> {
+        self.fetch_all("/api/v1/bookmarks?limit=40", max_pages).await
+    }
+
+    /// The token's favourites, newest first, for up to `max_pages` pages.
+    pub async fn favourites(&self, max_pages: usize) -> Result<Vec<Status>> {
+        self.fetch_all("/api/v1/favourites?limit=40", max_pages).await
+    }
+
+    // --- Conversations ---
+
+    /// The newest direct message threads.
+    pub async fn conversations(&self) -> Result<Vec<Conversation>> {
+        self.fetch("/api/v1/conversations?limit=20").await
+    }
+
+    /// Removes a conversation from the list; the statuses themselves stay.
+    pub async fn remove_conversation(&self, id: &str) -> Result<()> {
+        self.delete(&format!("/api/v1/conversations/{}", id)).await
+    }
+
+    // --- Instance ---
+
+    pub async fn instance(&self) -> Result<Instance> {
+        self.fetch("/api/v2/instance").await
+    }
+
+    // --- Search ---
+
+    /// Resolves a status URL (possibly on another server) through `/api/v2/search`.
+    pub async fn resolve_status(&self, url: &str) -> Result<Option<Status>> {
+        #[derive(serde::Deserialize)]
+        struct Results {
+            statuses: Vec<Status>,
+        }
+        let search = self.url("/api/v2/search");
+        let query = [("q", url), ("type", "statuses"), ("resolve", "true"), ("limit", "1")];
+        let results: Results = self.send(self.http.get(&search).query(&query), &search).await?.json().await?;
+        Ok(results.statuses.into_iter().next())
+    }
+
+    // --- Media ---
+
+    /// Uploads a file through `/api/v2/media` and waits until the server has processed it.
+    pub async fn upload_media(&self, bytes: Vec<u8>, filename: &str, mime: &str, description: Option<&str>) -> Result<Attachment> {
+        let part = multipart::Part::bytes(bytes).file_name(filename.to_string()).mime_str(mime)?;
+        let mut form = multipart::Form::new().part("file", part);
+        if let Some(description) = description {
+            form = form.text("description", description.to_string());
+        }
+        let url = self.url("/api/v2/media");
+        let mut media: Attachment = self.send(self.http.post(&url).multipart(form), &url).await?.json().await?;
+
+        // Large files are processed asynchronously (202 without a URL); they can't be attached until done
+        for _ in 0..MEDIA_POLLS {
+            if media.url.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            media = self.fetch(&format!("/api/v1/media/{}", media.id)).await?;
+        }
+        if media.url.is_none() {
+            anyhow::bail!("{} was still processing media {} after {}s", url, media.id, MEDIA_POLLS * 2);
+        }
+        Ok(media)
+    }
+
+    // --- Plumbing ---
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn action(&self, id: &str, action: &str) -> Result<Status> {
+        let url = self.url(&format!("/api/v1/statuses/{}/{}", id, action));
+        Ok(self.send(self.http.post(&url), &url).await?.json().await?)
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = self.url(path);
+        Ok(self.send(self.http.get(&url), &url).await?.json().await?)
+    }
+
+    async fn fetch_all<T: DeserializeOwned>(&self, path: &str, max_pages: usize) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = Some(self.url(path));
+        let mut pages = 0;
+
+        while let Some(url) = next.take() {
+            let resp = self.send(self.http.get(&url), &url).await?;
+            // A server pointing back at the same page would otherwise be fetched until max_pages
+            next = next_link(&resp).filter(|n| *n != url);
+            let page: Vec<T> = resp.json().await?;
+            if page.is_empty() {
+                break;
+            }
+            items.extend(page);
+
+            pages += 1;
+            if pages >= max_pages {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Sends an authenticated request, turning non-2xx answers into [`ApiError`]s.
+    async fn send(&self, req: RequestBuilder, url: &str) -> Result<Response> {
+        let resp = req.header("Authorization", format!("Bearer {}", self.token)).send().await
+            .with_context(|| format!("Couldn't reach {}", url))?;
+        check(resp, url).await
+    }
+}
+
+async fn check(resp: Response, url: &str) -> Result<Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body: String = resp.text().await.unwrap_or_default().chars().take(MAX_ERROR_BODY).collect();
+    Err(ApiError { url: url.to_string(), status, body }.into())
+}
+
+/// Extracts the `rel="next"` URL from a response's RFC 5988 Link header.
+fn next_link(resp: &Response) -> Option<String> {
+    next_in_link_header(resp.headers().get(LINK)?.to_str().ok()?)
+}
+
+fn next_in_link_header(header: &str) -> Option<String> {
+    static NEXT: OnceLock<Regex> = OnceLock::new();
+    let re = NEXT.get_or_init(|| Regex::new(r#"<([^>]+)>\s*;\s*rel="?next"?"#).unwrap());
+    re.captures(header).map(|cap| cap[1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn next_link_is_found_among_others() {
+        let header = r#"<https://m.example/api/v1/bookmarks?max_id=7>; rel="next", <https://m.example/api/v1/bookmarks?min_id=9>; rel="prev""#;
+        assert_eq!(next_in_link_header(header).as_deref(), Some("https://m.example/api/v1/bookmarks?max_id=7"));
+        let reversed = r#"<https://m.example/a?min_id=9>; rel="prev", <https://m.example/a?max_id=7>;rel=next"#;
+        assert_eq!(next_in_link_header(reversed).as_deref(), Some("https://m.example/a?max_id=7"));
+    }
+
+    #[test]
+    fn no_next_link_on_the_last_page() {
+        assert_eq!(next_in_link_header(r#"<https://m.example/a?min_id=9>; rel="prev""#), None);
+        assert_eq!(next_in_link_header(""), None);
+    }
+
+    /// Serves `pages` (path -> (JSON body, next path)) on a local port and returns its base URL.
+    async fn serve(pages: Vec<(&'static str, &'static str, Option<&'static str>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let link_base = base.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).await.unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).await.unwrap() > 2 {
+                    header.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (body, next) = pages.iter()
+                    .find(|(p, _, _)| *p == path)
+                    .map(|(_, body, next)| (*body, *next))
+                    .unwrap_or(("[]", None));
+                let link = next.map(|n| format!("Link: <{}{}>; rel=\"next\"\r\n", link_base, n)).unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    link, body.len(), body
+                );
+                let mut stream = reader.into_inner();
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn fetch_all_follows_next_links_to_the_end() {
+        let base = serve(vec![
+            ("/list", "[1, 2]", Some("/list?page=2")),
+            ("/list?page=2", "[3]", None),
+        ]).await;
+        let items: Vec<u32> = Client::new(&base, "t").fetch_all("/list", 10).await.unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn fetch_all_stops_at_an_empty_page() {
+        let base = serve(vec![
+            ("/list", "[1]", Some("/list?page=2")),
+            ("/list?page=2", "[]", Some("/list?page=3")),
+            ("/list?page=3", "[9]", None),
+        ]).await;
+        let items: Vec<u32> = Client::new(&base, "t").fetch_all("/list", 10).await.unwrap();
+        assert_eq!(items, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn fetch_all_stops_at_max_pages() {
+        let base = serve(vec![
+            ("/list", "[1]", Some("/list?page=2")),
+            ("/list?page=2", "[2]", Some("/list?page=3")),
+            ("/list?page=3", "[3]", None),
+        ]).await;
+        let items: Vec<u32> = Client::new(&base, "t").fetch_all("/list", 2).await.unwrap();
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn fetch_all_stops_when_next_points_back_at_the_same_page() {
+        let base = serve(vec![("/list", "[1]", Some("/list"))]).await;
+        let items: Vec<u32> = Client::new(&base, "t").fetch_all("/list", 50).await.unwrap();
+        assert_eq!(items, vec![1]);
+    }
+}
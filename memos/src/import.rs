@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use bot_support::health::Metrics;
+
+use crate::links;
+use crate::mastodon::{self, Mastodon, Status};
+use crate::sink::Sink;
+use crate::state::SyncState;
+use crate::template::Layout;
+
+/// Statuses requested per page when importing through the API (Mastodon's maximum).
+const PAGE_SIZE: usize = 40;
+/// The ActivityPub audience that makes a post public.
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Where the history comes from.
+pub enum Source {
+    /// The account's statuses, paged through `/api/v1/accounts/:id/statuses`
+    Api,
+    /// An unpacked Mastodon archive export (`outbox.json`, `actor.json`, `media_attachments/`)
+    Archive(PathBuf),
+}
+
+impl Source {
+    /// `import` alone pages through the API, `import <dir>` reads an archive.
+    pub fn from_args(args: &[String]) -> Self {
+        match args.first() {
+            Some(dir) => Source::Archive(PathBuf::from(dir)),
+            None => Source::Api,
+        }
+    }
+}
+
+/// Everything a one-shot import needs besides the history itself.
+pub struct Importer<'a> {
+    pub mastodon: &'a Mastodon,
+    pub sink: &'a Sink,
+    pub layout: &'a Layout,
+    pub upload_media: bool,
+    pub metrics: &'a Metrics,
+    /// Memo visibility for imported posts (MEMOS_IMPORT_VISIBILITY, default PRIVATE)
+    pub visibility: String,
+    /// Also import direct messages, always as PRIVATE memos (MEMOS_IMPORT_DIRECT, off by default)
+    pub direct: bool,
+}
+
+impl Importer<'_> {
+    /// Imports the whole history of `my_id`, skipping statuses already in the sync state, and
+    /// backdates every memo to the original post.
+    pub async fn run(&self, source: &Source, my_id: &str, state: &mut SyncState) -> Result<()> {
+        let (mut imported, mut skipped) = (0, 0);
+        match source {
+            Source::Api => {
+                let mut max_id: Option<String> = None;
+                loop {
+                    let limit = PAGE_SIZE.to_string();
+                    let mut query = vec![("limit", limit.as_str())];
+                    if let Some(id) = &max_id {
+                        query.push(("max_id", id));
+                    }
+                    let statuses = match self.mastodon.account_statuses(my_id, &query).await {
+                        Ok(page) => page,
+                        Err(e) if mastodon::is_rate_limited(&e) => {
+                            warn!("⏳ Rate limited by Mastodon, retrying in 5 minutes");
+                            self.metrics.rate_limited();
+                            sleep(Duration::from_secs(300)).await;
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    let Some(last) = statuses.last() else { break };
+                    max_id = Some(last.id.clone());
+
+                    for status in &statuses {
+                        let Some(visibility) = self.memo_visibility(&status.visibility) else {
+                            skipped += 1;
+                            continue;
+                        };
+                        if state.synced.contains_key(&status.id) {
+                            skipped += 1;
+                            continue;
+                        }
+                        // Boosts are saved as the boosted post, dated when they were boosted
+                        let post = status.reblog.as_deref().unwrap_or(status);
+                        let rendered = crate::render_status(post, self.layout, self.sink, self.upload_media, self.metrics).await;
+                        if self.save(&status.id, post, rendered, visibility, Some(&status.created_at), state).await {
+                            imported += 1;
+                        }
+                    }
+                    info!(imported, skipped, "📥 Import in progress");
+                }
+            }
+            Source::Archive(dir) => {
+                let outbox = read_json(&dir.join("outbox.json"))?;
+                let actor = read_json(&dir.join("actor.json")).unwrap_or(Value::Null);
+                let mut activities = outbox["orderedItems"].as_array().cloned()
+                    .context("outbox.json has no orderedItems")?;
+                activities.sort_by(|a, b| a["published"].as_str().cmp(&b["published"].as_str()));
+
+                for activity in &activities {
+                    let id = archive_status_id(activity["id"].as_str().unwrap_or_default());
+                    let audience = archived_visibility(activity);
+                    let Some(visibility) = self.memo_visibility(audience) else {
+                        skipped += 1;
+                        continue;
+                    };
+                    if id.is_empty() || state.synced.contains_key(&id) {
+                        skipped += 1;
+                        continue;
+                    }
+                    let (rendered, post) = match activity["type"].as_str() {
+                        Some("Create") if activity["object"].is_object() => {
+                            let mut post = links::activitypub_to_status(&activity["object"], &actor, "");
+                            post.visibility = audience.to_string();
+                            (self.render_archived(&post, dir).await, post)
+                        }
+                        // Archives only keep the URL of boosted posts, so fetch them again
+                        Some("Announce") => {
+                            let url = activity["object"].as_str().unwrap_or_default();
+                            let Some(post) = links::resolve(self.mastodon, url).await else {
+                                warn!(url = %url, "⚠️ Couldn't fetch a boosted post, skipping it");
+                                skipped += 1;
+                                continue;
+                            };
+                            (crate::render_status(&post, self.layout, self.sink, self.upload_media, self.metrics).await, post)
+                        }
+                        _ => {
+                            skipped += 1;
+                            continue;
+                        }
+                    };
+                    if self.save(&id, &post, rendered, visibility, activity["published"].as_str(), state).await {
+                        imported += 1;
+                        if imported % PAGE_SIZE == 0 {
+                            info!(imported, skipped, "📥 Import in progress");
+                        }
+                    }
+                }
+            }
+        }
+        info!(imported, skipped, "🏁 Import finished");
+        Ok(())
+    }
+
+    /// The memo visibility for a post of the given Mastodon visibility, or `None` to skip it.
+    fn memo_visibility(&self, visibility: &str) -> Option<&str> {
+        match visibility {
+            "direct" if self.direct => Some("PRIVATE"),
+            "direct" => None,
+            _ => Some(&self.visibility),
+        }
+    }
+
+    /// Renders an archived post, uploading its media from the archive's own files.
+    ///
+    /// With MEMOS_UPLOAD_MEDIA off the media is left out: the archive's files have no URL to link.
+    async fn render_archived(&self, post: &Status, dir: &Path) -> (String, Vec<String>) {
+        let mut resources = Vec::new();
+        let mut captions = Vec::new();
+        for media in post.media_attachments.iter().filter(|_| self.upload_media) {
+            let path = media.url.as_deref().unwrap_or_default();
+            let file = dir.join(path.trim_start_matches('/'));
+            let filename = path.rsplit('/').next().unwrap_or("file");
+            // Archived attachments carry their MIME type as the kind
+            let mime = Some(media.kind.as_str()).filter(|m| m.contains('/')).unwrap_or("application/octet-stream");
+            let uploaded = match std::fs::read(&file) {
+                Ok(bytes) => self.sink.upload_resource(filename, mime, &bytes).await,
+                Err(e) => Err(e.into()),
+            };
+            match uploaded {
+                Ok(name) => {
+                    self.metrics.media_uploaded();
+                    resources.push(name);
+                    captions.extend(crate::media::context(media, None));
+                }
+                Err(e) => {
+                    warn!(file = %file.display(), error = %e, "⚠️ Couldn't upload archived media");
+                    self.metrics.failure(e);
+                }
+            }
+        }
+        let context = crate::status_context(post, captions, &self.layout.tag_policy);
+        (self.layout.body.render(&context), resources)
+    }
+
+    /// Creates the memo, backdates it and records it in the sync state. Returns whether it was saved.
+    ///
+    /// `rendered` is the memo body and its media, as returned by the renderers.
+    async fn save(&self, id: &str, post: &Status, rendered: (String, Vec<String>), visibility: &str, created_at: Option<&str>, state: &mut SyncState) -> bool {
+        let (content, resources) = rendered;
+        let tags = self.layout.tags.render(&crate::status_context(post, Vec::new(), &self.layout.tag_policy));
+        let payload = format!("{}\n\n{}", content, tags.trim());
+        let name = match crate::save_memo(self.sink, &payload, &resources, visibility, post).await {
+            Ok(name) => name,
+            Err(e) => {
+                error!(status_id = %id, error = %e, "❌ Couldn't import the post");
+                self.metrics.failure(e);
+                return false;
+            }
+        };
+        if let Some(created_at) = created_at {
+            if let Err(e) = self.sink.set_create_time(&name, created_at).await {
+                warn!(memo = %name, error = %e, "⚠️ Imported, but couldn't keep the original date");
+            }
+        }
+        self.metrics.post_ok();
+        state.synced.insert(id.to_string(), name);
+        if let Err(e) = state.save() {
+            error!(error = %e, "❌ Couldn't save the sync state");
+        }
+        true
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("{} isn't valid JSON", path.display()))
+}
+
+/// Mastodon's visibility for an archived activity, read from its `to` and `cc` audiences.
+fn archived_visibility(activity: &Value) -> &'static str {
+    let audience = |field: &str| -> Vec<&str> {
+        match &activity[field] {
+            Value::String(one) => vec![one.as_str()],
+            list => list.as_array().into_iter().flatten().filter_map(Value::as_str).collect(),
+        }
+    };
+    let (to, cc) = (audience("to"), audience("cc"));
+    let public = |list: &[&str]| list.iter().any(|a| matches!(*a, PUBLIC | "as:Public" | "Public"));
+    if public(&to) {
+        "public"
+    } else if public(&cc) {
+        "unlisted"
+    } else if to.iter().chain(&cc).any(|a| a.ends_with("/followers")) {
+        "private"
+    } else {
+        "direct"
+    }
+}
+
+/// The status ID behind an archived activity: `…/statuses/123/activity` is status `123`,
+/// the same ID the API and the triggers use.
+fn archive_status_id(activity_id: &str) -> String {
+    activity_id.trim_end_matches("/activity").rsplit('/').next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn archived_visibility_follows_the_audience() {
+        let followers = "https://example.social/users/jane/followers";
+        let mention = "https://other.example/users/bob";
+        assert_eq!(archived_visibility(&json!({ "to": [PUBLIC], "cc": [followers] })), "public");
+        assert_eq!(archived_visibility(&json!({ "to": [followers], "cc": [PUBLIC] })), "unlisted");
+        assert_eq!(archived_visibility(&json!({ "to": followers, "cc": [mention] })), "private");
+        assert_eq!(archived_visibility(&json!({ "to": [mention], "cc": [] })), "direct");
+    }
+}
@@ -0,0 +1,250 @@
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+use crate::mastodon::{Account, Attachment, Mastodon, Poll, PollOption, Status};
+
+/// Every link in a status's HTML that points at a single post, in order, without duplicates.
+///
+/// Mention and hashtag links (by their `class` and `rel` tokens) are skipped, so profile links
+/// never count as posts.
+pub fn status_urls(html: &str) -> Vec<String> {
+    static ANCHOR: OnceLock<Regex> = OnceLock::new();
+    let anchor = ANCHOR.get_or_init(|| Regex::new(r#"<a\s[^>]*>"#).unwrap());
+
+    let mut urls: Vec<String> = Vec::new();
+    for tag in anchor.find_iter(html).map(|m| m.as_str()) {
+        let has_token = |name: &str, tokens: &[&str]| {
+            attribute(tag, name).is_some_and(|v| v.split_whitespace().any(|t| tokens.contains(&t)))
+        };
+        if has_token("class", &["mention", "hashtag"]) || has_token("rel", &["tag"]) {
+            continue;
+        }
+        if let Some(url) = attribute(tag, "href").map(|v| v.replace("&amp;", "&")) {
+            if looks_like_status_url(&url) && !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    }
+    urls
+}
+
+/// The value of attribute `name` in a raw start tag like `<a href="…" class='…'>`.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let attr = ATTR.get_or_init(|| Regex::new(r#"([A-Za-z_:][-A-Za-z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap());
+    attr.captures_iter(tag)
+        .find(|c| c[1].eq_ignore_ascii_case(name))
+        .and_then(|c| c.get(2).or(c.get(3)).or(c.get(4)))
+        .map(|m| m.as_str())
+}
+
+/// What identifies a post across our server and the wider fediverse: its status ID, or for
+/// posts fetched over ActivityPub (which have none) the object's `uri`, then its URL.
+pub fn post_key(status: &Status) -> &str {
+    [status.id.as_str(), status.uri.as_str(), status.url.as_deref().unwrap_or_default()]
+        .into_iter()
+        .find(|key| !key.is_empty())
+        .unwrap_or_default()
+}
+
+/// True for URL shapes used by fediverse software for a single post:
+/// Mastodon (`/@user/123`, `/users/user/statuses/123`), GoToSocial (`/@user/statuses/01H…`),
+/// Pleroma/Akkoma (`/notice/…`, `/objects/…`), Misskey and forks (`/notes/…`) and Friendica (`/display/…`).
+pub fn looks_like_status_url(url: &str) -> bool {
+    static STATUS: OnceLock<Regex> = OnceLock::new();
+    let re = STATUS.get_or_init(|| {
+        Regex::new(concat!(
+            r"^https?://[^/\s]+/(",
+            r"@[^/\s]+/\d+",
+            r"|@[^/\s]+/statuses/[0-9A-Za-z]+",
+            r"|users/[^/\s]+/statuses/[0-9A-Za-z]+",
+            r"|notice/[0-9A-Za-z]+",
+            r"|objects/[0-9A-Za-z-]+",
+            r"|notes/[0-9a-z]+",
+            r"|display/[0-9A-Za-z-]+",
+            r")/?([?#].*)?$",
+        )).unwrap()
+    });
+    re.is_match(url)
+}
+
+/// Resolves a post URL to a status: through our server's search first, then by fetching the
+/// ActivityPub object directly (for posts our server can't or won't resolve).
+pub async fn resolve(mastodon: &Mastodon, url: &str) -> Option<Status> {
+    match mastodon.resolve_status(url).await {
+        Ok(Some(status)) => return Some(status),
+        Ok(None) => debug!(url = %url, "Search didn't resolve the post, fetching it over ActivityPub"),
+        Err(e) => debug!(url = %url, error = %e, "Search failed, fetching the post over ActivityPub"),
+    }
+
+    let object = match mastodon.fetch_activitypub(url).await {
+        Ok(object) => object,
+        Err(e) => {
+            warn!(url = %url, error = %e, "⚠️ Couldn't resolve the linked post");
+            return None;
+        }
+    };
+    if object["content"].as_str().is_none() {
+        warn!(url = %url, kind = %object["type"], "⚠️ Linked URL isn't a post");
+        return None;
+    }
+
+    let actor = match object["attributedTo"].as_str() {
+        Some(actor_url) => mastodon.fetch_activitypub(actor_url).await.unwrap_or(Value::Null),
+        None => Value::Null,
+    };
+    Some(activitypub_to_status(&object, &actor, url))
+}
+
+/// Maps an ActivityPub Note and its actor onto the parts of a Mastodon status the memo
+/// renderer reads.
+///
+/// `id` is deliberately empty: the post isn't known to our server, so there is no status to
+/// fetch a thread, poll or edits for. `uri` carries the ActivityPub id instead (see [`post_key`]).
+pub fn activitypub_to_status(object: &Value, actor: &Value, fallback_url: &str) -> Status {
+    let text = |v: &Value| v.as_str().unwrap_or_default().to_string();
+    let username = actor["preferredUsername"].as_str().unwrap_or("unknown");
+    let actor_url = actor["url"].as_str().or(actor["id"].as_str()).unwrap_or_default();
+    let host = actor_url.split("://").nth(1).and_then(|rest| rest.split('/').next()).unwrap_or_default();
+
+    let media = object["attachment"].as_array().into_iter().flatten()
+        .filter_map(|a| {
+            let url = a["url"].as_str().or(a["url"][0]["href"].as_str())?;
+            Some(Attachment {
+                kind: text(&a["mediaType"]),
+                url: Some(url.to_string()),
+                description: a["name"].as_str().map(str::to_string),
+                ..Default::default()
+            })
+        })
+        .collect();
+    let url = object["url"].as_str().or(object["id"].as_str()).unwrap_or(fallback_url);
+
+    Status {
+        uri: object["id"].as_str().unwrap_or(url).to_string(),
+        url: Some(url.to_string()),
+        content: text(&object["content"]),
+        spoiler_text: text(&object["summary"]),
+        created_at: text(&object["published"]),
+        account: Account {
+            display_name: actor["name"].as_str().unwrap_or(username).to_string(),
+            username: username.to_string(),
+            acct: format!("{}@{}", username, host),
+            avatar: text(&actor["icon"]["url"]),
+            url: actor_url.to_string(),
+            ..Default::default()
+        },
+        media_attachments: media,
+        poll: activitypub_poll(object),
+        ..Default::default()
+    }
+}
+
+/// Maps an ActivityPub Question onto the Mastodon poll shape: `oneOf` holds the options of a
+/// single-choice poll, `anyOf` those of a multiple-choice one.
+fn activitypub_poll(object: &Value) -> Option<Poll> {
+    let (options, multiple) = match (object["oneOf"].as_array(), object["anyOf"].as_array()) {
+        (Some(options), _) => (options, false),
+        (None, Some(options)) => (options, true),
+        (None, None) => return None,
+    };
+    let options: Vec<PollOption> = options.iter()
+        .map(|o| PollOption {
+            title: o["name"].as_str().unwrap_or_default().to_string(),
+            votes_count: o["replies"]["totalItems"].as_u64(),
+        })
+        .collect();
+    let ends = object["endTime"].as_str().or(object["closed"].as_str());
+    let expired = !object["closed"].is_null()
+        || ends.and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok()).is_some_and(|at| at <= chrono::Utc::now());
+
+    Some(Poll {
+        votes_count: options.iter().filter_map(|o| o.votes_count).sum(),
+        voters_count: object["votersCount"].as_u64(),
+        options,
+        multiple,
+        expired,
+        expires_at: ends.map(str::to_string),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_url_shapes() {
+        for url in [
+            "https://mastodon.social/@user/123",
+            "https://mastodon.social/@user@remote.example/123",
+            "https://gts.example/@user/statuses/01HQ5ZJ2",
+            "https://mastodon.social/users/x/statuses/1",
+            "https://misskey.example/notes/9k2ab3cdef",
+            "https://akkoma.example/objects/2f1e7a6c-3b4d-4e5f-8a9b-0c1d2e3f4a5b",
+            "https://pleroma.example/notice/AbC123",
+            "https://friendica.example/display/abc-123",
+            "https://mastodon.social/@user/123/?utm=x",
+        ] {
+            assert!(looks_like_status_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn profiles_and_pages_are_not_statuses() {
+        for url in [
+            "https://mastodon.social/@user2",
+            "https://mastodon.social/@user",
+            "https://mastodon.social/users/x",
+            "https://mastodon.social/tags/rust",
+            "https://example.com/blog/123",
+            "https://mastodon.social/@user/123/reblogs",
+        ] {
+            assert!(!looks_like_status_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn mentions_and_hashtags_are_skipped_by_class() {
+        let html = concat!(
+            r#"<p><span class="h-card"><a href="https://mastodon.social/@user/123" class="u-url mention">@user</a></span> "#,
+            r#"<a href="https://mastodon.social/tags/rust" class="mention hashtag" rel="tag">#rust</a> "#,
+            r#"<a href="https://mastodon.social/@user/456" rel="tag">tagged</a></p>"#,
+        );
+        assert!(status_urls(html).is_empty());
+    }
+
+    #[test]
+    fn words_in_the_url_or_other_attributes_dont_hide_a_link() {
+        let html = concat!(
+            r#"<a href="https://mastodon.social/@hashtagfan/1234" rel="nofollow noopener" target="_blank">post</a> "#,
+            r#"<a href='https://mention.example/notes/9abc' class="link-mentioned">note</a>"#,
+        );
+        assert_eq!(status_urls(html), vec!["https://mastodon.social/@hashtagfan/1234", "https://mention.example/notes/9abc"]);
+    }
+
+    #[test]
+    fn every_status_link_is_kept_once_in_order() {
+        let html = concat!(
+            r#"<p><a href="https://a.example/@x/2">one</a> <a href="https://example.com/">site</a> "#,
+            r#"<a href="https://b.example/users/y/statuses/1">two</a> <a href="https://a.example/@x/2">again</a> "#,
+            r#"<a href="https://c.example/@z/3?a=1&amp;b=2">three</a></p>"#,
+        );
+        assert_eq!(status_urls(html), vec![
+            "https://a.example/@x/2",
+            "https://b.example/users/y/statuses/1",
+            "https://c.example/@z/3?a=1&b=2",
+        ]);
+    }
+
+    #[test]
+    fn activitypub_posts_are_keyed_by_their_object_id() {
+        let object = serde_json::json!({ "id": "https://a.example/objects/1", "url": "https://a.example/notice/1", "content": "<p>hi</p>" });
+        let status = activitypub_to_status(&object, &Value::Null, "https://a.example/fallback");
+        assert!(status.id.is_empty());
+        assert_eq!(post_key(&status), "https://a.example/objects/1");
+        assert_eq!(status.url.as_deref(), Some("https://a.example/notice/1"));
+    }
+}
@@ -1,193 +1,468 @@
+mod import;
+mod links;
+mod markdown;
+mod mastodon;
+mod media;
+mod memos_api;
+mod notify;
+mod polls;
+mod publish;
+mod sink;
+mod state;
+mod tags;
+mod template;
+mod thread;
+mod triggers;
+mod vault;
+mod watch;
+
 use dotenvy::dotenv;
-use html2md::parse_html;
-use megalodon::{generator, megalodon::SearchInputOptions};
 use reqwest::Client;
 use serde_json::{json, Value};
+use mastodon::Status;
 use std::env;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
-use regex::Regex;
+use bot_support::health::{self, Metrics};
+use mastodon::Mastodon;
+use notify::Notifier;
+use polls::PollResults;
+use publish::Publisher;
+use sink::Sink;
+use state::SyncState;
+use tags::TagPolicy;
+use template::Layout;
+use thread::ThreadMode;
+use watch::Watcher;
+use tracing::{error, info, warn};
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     // Initializing environment variables from .env file
     dotenv().ok();
+    bot_support::logging::init();
 
     let m_url = env::var("MASTODON_URL").expect("MASTODON_URL missing");
     let m_token = env::var("MASTODON_TOKEN").expect("MASTODON_TOKEN missing");
-    let memos_url = env::var("MEMOS_URL").expect("MEMOS_URL missing");
-    let memos_token = env::var("MEMOS_TOKEN").expect("MEMOS_TOKEN missing");
-
-    println!("==================================================");
-    println!("🚀 MASTODON TO MEMOS");
-    println!("==================================================");
 
-    // Initializing Mastodon client
-    let action_client = generator(
-        megalodon::SNS::Mastodon,
-        m_url.clone(),
-        Some(m_token.clone()),
-        None,
-    ).expect("Failed to create Mastodon client");
+    info!(version = env!("CARGO_PKG_VERSION"), "🚀 MASTODON TO MEMOS");
 
     let http_client = Client::new();
-    let status_url_regex = Regex::new(r#"https?://[^\s<"]+"#).unwrap();
+    let mastodon = Mastodon::with_http(http_client.clone(), &m_url, &m_token);
+    // For Memos servers that hide their version
+    let memos_version = env::var("MEMOS_VERSION").ok().filter(|v| !v.is_empty());
+    let sink = Sink::from_env(http_client.clone(), memos_version.as_deref()).await?;
+    let upload_media = env::var("MEMOS_UPLOAD_MEDIA").map(|v| v != "false").unwrap_or(true);
+    let thread_mode = ThreadMode::from_env()?;
+    let triggers = triggers::parse(
+        &env::var("MEMOS_TRIGGERS").unwrap_or_else(|_| "tag:memos".into()),
+        &env::var("MEMOS_CLEANUP").unwrap_or_default(),
+    )?;
+    let layout = Layout::from_env(&m_url, &triggers)?;
+    let reply_text = env::var("MEMOS_REPLY_TEXT").unwrap_or_else(|_| "saved ✅".into());
+    // Cleanups like `reply` or `none` (or a failed delete) leave the trigger in place,
+    // so remember what's already saved instead of relying on it disappearing
+    let mut state = SyncState::load().inspect_err(|e| error!(error = ?e, "❌ Refusing to start with an unreadable sync state"))?;
+    info!(synced = state.synced.len(), "📂 Loaded sync state");
+    let watcher = Watcher::from_env();
+    let notifier = Notifier::from_env()?;
+    let poll_results = PollResults::from_env();
+    let mut next_sweep = Instant::now();
+
+    // Optional /healthz + /metrics endpoint (unhealthy after ~5 missed polls)
+    let metrics = Metrics::new("memos", Duration::from_secs(300));
+    if let Ok(addr) = env::var("HEALTH_ADDR") {
+        health::serve(addr.clone(), metrics.clone()).await?;
+        info!(%addr, "🩺 Health endpoint listening");
+    }
 
-    let me = action_client.verify_account_credentials().await?.json;
-    println!("👤 Connected as: {} (ID: {})", me.username, me.id);
+    let me = mastodon.verify_credentials().await?;
+    info!(username = %me.username, id = %me.id, "👤 Connected to Mastodon");
+
+    // `mastodon2memos import [archive dir]` imports the whole history once and exits
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import") {
+        let importer = import::Importer {
+            mastodon: &mastodon,
+            sink: &sink,
+            layout: &layout,
+            upload_media,
+            metrics: &metrics,
+            visibility: env::var("MEMOS_IMPORT_VISIBILITY").unwrap_or_else(|_| "PRIVATE".into()),
+            direct: env::var("MEMOS_IMPORT_DIRECT").is_ok_and(|v| v == "true"),
+        };
+        return importer.run(&import::Source::from_args(&args[1..]), &me.id, &mut state).await;
+    }
 
-    let feed_endpoint = format!("{}/api/v1/accounts/{}/statuses?limit=5", m_url, me.id);
+    let publisher = match sink.memos() {
+        Some(_) => Publisher::from_env(&mastodon).await?,
+        None => {
+            if env::var("MEMOS_PUBLISH").is_ok_and(|v| v == "true") {
+                warn!("⚠️ Publishing needs a Memos server, ignoring MEMOS_PUBLISH for the vault");
+            }
+            None
+        }
+    };
 
     loop {
-        // Fetching timeline manually
-        let resp = http_client.get(&feed_endpoint)
-            .header("Authorization", format!("Bearer {}", m_token))
-            .send()
-            .await;
-
-        if let Ok(response) = resp {
-            if response.status().is_success() {
-                let statuses: Vec<Value> = response.json().await.unwrap_or_default();
-
-                for status in statuses {
-                    let status_id = status["id"].as_str().unwrap_or_default().to_string();
-
-                    let has_memos_tag = status["tags"].as_array()
-                        .map(|tags| tags.iter().any(|t| t["name"].as_str().unwrap_or("").to_lowercase() == "memos"))
-                        .unwrap_or(false);
-
-                    if has_memos_tag {
-                        println!("\n🎯 Found trigger post: {}", status_id);
-
-                        // Added 'author_username' to the extraction
-                        let (content_html, source_url, media_links, poll_info, author_header, author_username) =
-                            extract_full_data(&status, &*action_client, &status_url_regex).await;
-
-                        let mut markdown = parse_html(&content_html);
-
-                        markdown = markdown.replace("\\#", "#").replace("\\[", "[").replace("\\]", "]")
-                            .replace("\\*", "*").replace("\\_", "_").replace("\\>", ">").replace("\\`", "`");
+        let poll = triggers::collect(&mastodon, &triggers, &me.id, state.since_id.as_deref(), &state.bot_replies, &reply_text).await;
+        match poll.error {
+            Some(e) if mastodon::is_rate_limited(&e) => {
+                warn!("⏳ Rate limited by Mastodon, waiting for the next cycle");
+                metrics.rate_limited();
+            }
+            Some(e) => metrics.poll_failed(e),
+            None => metrics.poll_ok(),
+        }
+        let pending: Vec<_> = poll.captures.into_iter().filter(|c| !state.synced.contains_key(c.status_id())).collect();
+        metrics.set_queue_depth(pending.len());
+        let mut remaining = pending.len();
+        let mut all_saved = true;
+
+        for capture in pending {
+            let status_id = capture.status_id().to_string();
+            info!(status_id = %status_id, cleanup = ?capture.cleanup, "🎯 Found trigger post");
+
+            let (targets, unresolved) = resolve_targets(&capture.status, &mastodon, capture.follow_links).await;
+            // Saving only part of what the trigger asked for would clear it, so retry the whole capture
+            if !unresolved.is_empty() {
+                let e = anyhow::anyhow!("{}", unresolved.join(", "));
+                warn!(status_id = %status_id, error = %e, "⚠️ Couldn't resolve linked posts, leaving the trigger for the next cycle");
+                notifier.failure(&mastodon, &mut state, &capture.status, "Couldn't resolve some linked posts, the post is left untouched and retried every minute", &e).await;
+                if let Err(e) = state.save() {
+                    error!(error = %e, "❌ Couldn't save the sync state");
+                }
+                all_saved = false;
+                continue;
+            }
+            let tags = layout.tags.render(&status_context(&targets[0], Vec::new(), &layout.tag_policy));
 
-                        let mut final_payload = format!("{}\n\n", author_header);
+            let mut thread = Vec::new();
+            for target in &targets {
+                if thread_mode == ThreadMode::Off || target.id.is_empty() {
+                    thread.push(target.clone());
+                    continue;
+                }
+                match thread::fetch(&mastodon, target, &state.bot_replies).await {
+                    Ok(conversation) => thread.extend(conversation),
+                    Err(e) => {
+                        warn!(status_id = %status_id, error = %e, "⚠️ Couldn't fetch the conversation, saving the post alone");
+                        thread.push(target.clone());
+                    }
+                }
+            }
 
-                        final_payload.push_str(&markdown);
+            // MEMOS_TAGS for the saved post, plus any tags the trigger routes to
+            let mut tag_line = format!("\n\n{}", tags.trim());
+            for tag in capture.route.tags.iter().filter_map(|t| tags::normalize(t)) {
+                tag_line.push_str(&format!(" #{}", tag));
+            }
 
-                        if !poll_info.is_empty() {
-                            final_payload.push_str("\n\n📊 **Poll Options:**");
-                            final_payload.push_str(&poll_info);
+            let visibility = &capture.route.visibility;
+            // The memo each post of the thread ended up in
+            let mut memo_of: Vec<String> = Vec::new();
+            let memos_res = if thread_mode == ThreadMode::Linked {
+                // One memo per post, each referencing the one it replies to
+                let mut previous: Option<String> = None;
+                let mut result = Ok(String::new());
+                for post in &thread {
+                    // Posts saved by an earlier, partly failed attempt keep their memo
+                    let key = links::post_key(post).to_string();
+                    let saved = match state.thread_memos.get(&status_id).and_then(|memos| memos.get(&key)) {
+                        Some(name) => Ok(name.clone()),
+                        None => {
+                            let (content, resources) = render_status(post, &layout, &sink, upload_media, &metrics).await;
+                            let created = save_memo(&sink, &(content + &tag_line), &resources, visibility, post).await;
+                            if let Ok(name) = &created {
+                                if let Some(prev) = &previous {
+                                    if let Err(e) = sink.link_memos(name, prev).await {
+                                        warn!(memo = %name, related = %prev, error = %e, "⚠️ Couldn't link the thread memos");
+                                        metrics.failure(e);
+                                    }
+                                }
+                                state.thread_memos.entry(status_id.clone()).or_default().insert(key, name.clone());
+                                if let Err(e) = state.save() {
+                                    error!(error = %e, "❌ Couldn't save the sync state");
+                                }
+                            }
+                            created
                         }
-
-                        if !media_links.is_empty() {
-                            final_payload.push_str("\n\n🖼️ **Media:**\n");
-                            final_payload.push_str(&media_links);
+                    };
+                    match saved {
+                        Ok(name) => {
+                            if links::post_key(post) == links::post_key(&targets[0]) && result.as_ref().is_ok_and(String::is_empty) {
+                                result = Ok(name.clone());
+                            }
+                            memo_of.push(name.clone());
+                            previous = Some(name);
                         }
-
-                        if let Some(url) = source_url {
-                            final_payload.push_str(&format!("\n\n🔗 **Source:** {}", url));
+                        Err(e) => {
+                            result = Err(e);
+                            break;
                         }
+                    }
+                }
+                result
+            } else {
+                let mut parts = Vec::new();
+                let mut resources = Vec::new();
+                for post in &thread {
+                    let (content, copied) = render_status(post, &layout, &sink, upload_media, &metrics).await;
+                    parts.push(content);
+                    resources.extend(copied);
+                }
+                let final_payload = parts.join("\n\n---\n\n") + &tag_line;
+                let result = save_memo(&sink, &final_payload, &resources, visibility, &targets[0]).await;
+                if let Ok(name) = &result {
+                    memo_of = vec![name.clone(); thread.len()];
+                }
+                result
+            };
+
+            match memos_res {
+                Ok(memo_name) => {
+                    info!(status_id = %status_id, memo = %memo_name, posts = thread.len(), "✅ Sync successful!");
+                    metrics.post_ok();
+                    remaining -= 1;
+                    metrics.set_queue_depth(remaining);
+
+                    state.synced.insert(status_id.clone(), memo_name.clone());
+                    notifier.resolved(&mut state, &status_id);
+                    // Only other people's posts can change behind our back
+                    if let Some(watcher) = &watcher {
+                        for target in targets.iter().filter(|t| t.account.id != me.id) {
+                            if let Some(i) = thread.iter().position(|p| links::post_key(p) == links::post_key(target)) {
+                                watcher.track(&mut state, target, &memo_of[i]);
+                            }
+                        }
+                    }
+                    if let Some(poll_results) = &poll_results {
+                        for (post, memo) in thread.iter().zip(&memo_of) {
+                            poll_results.track(&mut state, post, memo);
+                        }
+                    }
+                    if let Err(e) = state.save() {
+                        error!(error = %e, "❌ Couldn't save the sync state");
+                    }
 
-                        // DYNAMIC HASHTAG GENERATION
-                        // Adds #mastodon #mastodon2memos AND #{username}
-                        final_payload.push_str(&format!("\n\n#mastodon #mastodon2memos #{}", author_username));
-
-                        println!("📦 Sending payload to Memos...");
-                        let memos_res = http_client.post(format!("{}/api/v1/memos", memos_url))
-                            .header("Authorization", format!("Bearer {}", memos_token))
-                            .json(&json!({
-                                "content": final_payload,
-                                "visibility": "PRIVATE"
-                            }))
-                            .send()
-                            .await;
-
-                        if let Ok(m_resp) = memos_res {
-                            if m_resp.status().is_success() {
-                                println!("✅ Sync successful!");
-
-                                let _ = if !status["reblog"].is_null() {
-                                    action_client.unreblog_status(status_id).await.map(|_| ())
-                                } else {
-                                    action_client.delete_status(status_id).await.map(|_| ())
-                                };
-                                break;
+                    // Never delete the original unless Memos can hand the memo back
+                    let verified = if capture.cleanup.is_destructive() {
+                        match sink.memo_exists(&memo_name).await {
+                            Ok(true) => true,
+                            Ok(false) => {
+                                warn!(status_id = %status_id, memo = %memo_name, "⚠️ Memo not found after saving, keeping the post");
+                                false
+                            }
+                            Err(e) => {
+                                warn!(status_id = %status_id, memo = %memo_name, error = %e, "⚠️ Couldn't verify the memo, keeping the post");
+                                false
                             }
                         }
+                    } else {
+                        true
+                    };
+
+                    if verified {
+                        match capture.cleanup.run(&mastodon).await {
+                            Ok(Some(reply_id)) => {
+                                state.bot_replies.insert(reply_id);
+                                if let Err(e) = state.save() {
+                                    error!(error = %e, "❌ Couldn't save the sync state");
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                error!(status_id = %status_id, cleanup = ?capture.cleanup, error = %e, "❌ Couldn't clear the trigger on Mastodon");
+                                metrics.failure(e);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!(status_id = %status_id, error = %e, "❌ Couldn't save the memo");
+                    notifier.failure(&mastodon, &mut state, &capture.status, "Couldn't save this post, it is left untouched and retried every minute", &e).await;
+                    if let Err(e) = state.save() {
+                        error!(error = %e, "❌ Couldn't save the sync state");
                     }
+                    metrics.failure(e);
+                    all_saved = false;
+                }
+            }
+        }
+
+        if let (Some(publisher), Some(memos)) = (&publisher, sink.memos()) {
+            let before = (state.published.len(), state.publish_seeded);
+            publisher.run(memos, &mastodon, &mut state, &metrics).await;
+            if (state.published.len(), state.publish_seeded) != before {
+                if let Err(e) = state.save() {
+                    error!(error = %e, "❌ Couldn't save the sync state");
                 }
             }
         }
+
+        if let Some(watcher) = &watcher {
+            if Instant::now() >= next_sweep {
+                watcher.sweep(&mastodon, &layout, &sink, &mut state, &metrics).await;
+                next_sweep = Instant::now() + watcher.interval;
+                if let Err(e) = state.save() {
+                    error!(error = %e, "❌ Couldn't save the sync state");
+                }
+            }
+        }
+
+        if let Some(poll_results) = &poll_results {
+            if poll_results.check(&mastodon, &sink, &mut state, &metrics).await {
+                if let Err(e) = state.save() {
+                    error!(error = %e, "❌ Couldn't save the sync state");
+                }
+            }
+        }
+
+        // Only move past the scanned timeline once every trigger in it is safely stored
+        if all_saved && poll.newest_own_id.is_some() && poll.newest_own_id != state.since_id {
+            state.since_id = poll.newest_own_id;
+            if let Err(e) = state.save() {
+                error!(error = %e, "❌ Couldn't save the sync state");
+            }
+        }
         sleep(Duration::from_secs(60)).await;
     }
 }
 
-// Updated signature to return author_username (String) at the end
-async fn extract_full_data(
-    status: &Value,
-    client: &dyn megalodon::Megalodon,
-    regex: &Regex
-) -> (String, Option<String>, String, String, String, String) {
-    let mut target_status = status;
-
-    if !status["reblog"].is_null() {
-        target_status = &status["reblog"];
-    } else {
-        let content = status["content"].as_str().unwrap_or("");
-        if let Some(mat) = regex.find(content) {
-            let url = mat.as_str();
-            if url.contains("/@") && url.chars().any(|c| c.is_numeric()) {
-                let opts = SearchInputOptions { resolve: Some(true), ..Default::default() };
-                if let Ok(res) = client.search(url.to_string(), Some(&opts)).await {
-                    if let Some(s) = res.json.statuses.first() {
-                        let media = s.media_attachments.iter()
-                            .map(|m| format!("![]({})", m.url))
-                            .collect::<Vec<_>>()
-                            .join("\n");
-
-                        let header = format!(
-                            "<img src=\"{}\" width=\"32\" height=\"32\" style=\"border-radius:4px; vertical-align:middle; display:inline-block; margin:0 8px 0 0;\"><span style=\"vertical-align:middle;\">**{}** (@{})</span>",
-                            s.account.avatar, s.account.display_name, s.account.username
-                        );
-
-                        // Return username as the last element
-                        return (s.content.clone(), s.url.clone(), media, String::new(), header, s.account.username.clone());
+/// Picks the statuses to save: the boosted post, every post a trigger links to, or the status
+/// itself. Also returns the links that couldn't be resolved.
+async fn resolve_targets(status: &Status, mastodon: &Mastodon, follow_links: bool) -> (Vec<Status>, Vec<String>) {
+    if let Some(boosted) = &status.reblog {
+        return (vec![(**boosted).clone()], Vec::new());
+    }
+    let mut unresolved = Vec::new();
+    if follow_links {
+        let mut linked = Vec::new();
+        for url in links::status_urls(&status.content) {
+            match links::resolve(mastodon, &url).await {
+                Some(post) => linked.push(post),
+                None => unresolved.push(url),
+            }
+        }
+        if !linked.is_empty() {
+            return (linked, unresolved);
+        }
+    }
+    (vec![status.clone()], unresolved)
+}
+
+/// Renders one status through the memo template and returns it with the names of the media
+/// resources copied into Memos.
+async fn render_status(status: &Status, layout: &Layout, sink: &Sink, upload_media: bool, metrics: &Metrics) -> (String, Vec<String>) {
+    // Store attachments in Memos itself so the archive survives remote cache pruning;
+    // anything that can't be copied is hotlinked as before
+    let mut resources = Vec::new();
+    let mut media = Vec::new();
+    'attachments: for attachment in &status.media_attachments {
+        let sources = media::sources(attachment);
+        let Some(&m_url) = sources.first() else {
+            continue;
+        };
+        if upload_media {
+            for url in &sources {
+                match sink.copy_remote_file(url).await {
+                    Ok(name) => {
+                        metrics.media_uploaded();
+                        resources.push(name);
+                        media.extend(media::context(attachment, None));
+                        continue 'attachments;
+                    }
+                    Err(e) => {
+                        warn!(url = %url, error = %e, "⚠️ Couldn't copy media into Memos");
+                        metrics.failure(e);
                     }
                 }
             }
+            warn!(url = %m_url, "⚠️ Linking the remote file instead");
         }
+        media.extend(media::context(attachment, Some(m_url)));
     }
 
-    let html = target_status["content"].as_str().unwrap_or("").to_string();
-    let url = target_status["url"].as_str().map(|s| s.to_string());
+    (layout.body.render(&status_context(status, media, &layout.tag_policy)), resources)
+}
 
-    let author_name = target_status["account"]["display_name"].as_str().unwrap_or("Unknown");
-    let author_handle = target_status["account"]["username"].as_str().unwrap_or("unknown"); // This is the pure username
-    let author_avatar = target_status["account"]["avatar"].as_str().unwrap_or("");
+/// Everything a memo template can use about a status, already converted to Markdown.
+///
+/// `media` holds the attachments that are linked, and the alt text of those stored in Memos.
+fn status_context(status: &Status, media: Vec<Value>, tag_policy: &TagPolicy) -> Value {
+    let account = &status.account;
+    let mut content = markdown::to_markdown(&status.content, &status.emojis, tag_policy);
+    if let Some(quoted) = quoted_status(status) {
+        content.push_str("\n\n");
+        content.push_str(&render_quote(&quoted, tag_policy));
+    }
 
-    let author_header = format!(
-        "<img src=\"{}\" width=\"32\" height=\"32\" style=\"border-radius:4px; vertical-align:middle; display:inline-block; margin:0 8px 0 0;\"><span style=\"vertical-align:middle;\">**{}** (@{})</span>",
-        author_avatar, author_name, author_handle
-    );
+    let card = status.card.as_ref().map(|card| {
+        let title = Some(card.title.as_str()).filter(|t| !t.is_empty()).unwrap_or(&card.url);
+        let description = markdown::plain_to_markdown(card.description.trim(), &[]);
+        json!({
+            "url": markdown::destination(&card.url),
+            "title": markdown::plain_to_markdown(title, &[]),
+            "quote": markdown::blockquote(&description),
+            "description": description,
+            "image": card.image.as_deref().map(markdown::destination),
+        })
+    });
+
+    json!({
+        "author": {
+            "name": markdown::plain_to_markdown(or_else(&account.display_name, "Unknown"), &account.emojis),
+            "username": or_else(&account.username, "unknown"),
+            "tag": tag_policy.author_tag(account),
+            "acct": account.acct,
+            "avatar": account.avatar,
+            "url": account.url,
+        },
+        "hashtags": tag_policy.status_tags(status),
+        // On one line, so it fits the CW line of a template
+        "spoiler": markdown::plain_to_markdown(&status.spoiler_text.split_whitespace().collect::<Vec<_>>().join(" "), &status.emojis),
+        "content": content,
+        "poll": polls::context(status),
+        "media": media,
+        "card": card,
+        "url": status.url,
+        "created_at": status.created_at,
+        "visibility": status.visibility,
+    })
+}
 
-    let mut media_links = String::new();
-    if let Some(attachments) = target_status["media_attachments"].as_array() {
-        for anim in attachments {
-            if let Some(m_url) = anim["url"].as_str() {
-                media_links.push_str(&format!("![]({})\n", m_url));
-            }
-        }
-    }
+/// `value`, or `fallback` when it's empty.
+fn or_else<'a>(value: &'a str, fallback: &'a str) -> &'a str {
+    if value.is_empty() { fallback } else { value }
+}
 
-    let mut poll_info = String::new();
-    if !target_status["poll"].is_null() {
-        if let Some(options) = target_status["poll"]["options"].as_array() {
-            for opt in options {
-                let title = opt["title"].as_str().unwrap_or("");
-                let votes = opt["votes_count"].as_u64().unwrap_or(0);
-                poll_info.push_str(&format!("\n* {} ({} votes)", title, votes));
-            }
+/// The post a status quotes: Mastodon 4.4+ wraps it in `quote.quoted_status`, some forks
+/// embed the status directly as `quote`. Quotes aren't part of the typed status yet.
+fn quoted_status(status: &Status) -> Option<Status> {
+    let quote = status.extra.get("quote")?;
+    [&quote["quoted_status"], quote].into_iter()
+        .filter(|q| q["content"].is_string())
+        .find_map(|q| serde_json::from_value(q.clone()).ok())
+}
+
+/// Renders a quoted post as a Markdown blockquote (author, text, media links, source).
+fn render_quote(quoted: &Status, tag_policy: &TagPolicy) -> String {
+    let name = markdown::plain_to_markdown(or_else(&quoted.account.display_name, "Unknown"), &quoted.account.emojis);
+    let mut text = format!("💬 **{}** (@{})\n\n", name, or_else(&quoted.account.acct, "unknown"));
+    text.push_str(&markdown::to_markdown(&quoted.content, &quoted.emojis, tag_policy));
+    for attachment in &quoted.media_attachments {
+        if let Some(url) = media::sources(attachment).first() {
+            text.push_str(&format!("\n\n{}", media::embed(attachment, url)));
         }
     }
+    if let Some(url) = &quoted.url {
+        text.push_str(&format!("\n\n{}", url));
+    }
+    markdown::blockquote(&text)
+}
 
-    // Return author_handle (string) as the last element
-    (html, url, media_links, poll_info, author_header, author_handle.to_string())
+/// Creates a memo for `source` with the copied media attached.
+async fn save_memo(sink: &Sink, content: &str, resources: &[String], visibility: &str, source: &Status) -> anyhow::Result<String> {
+    info!(bytes = content.len(), visibility = %visibility, "📦 Sending payload to Memos...");
+    sink.create_memo(content, visibility, resources, source).await
 }
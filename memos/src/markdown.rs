@@ -0,0 +1,359 @@
+use html5ever::tendril::TendrilSink;
+use html5ever::{local_name, namespace_url, ns, parse_fragment, ParseOpts, QualName};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::mastodon::Emoji;
+use crate::tags::{self, TagPolicy};
+
+/// Converts Mastodon status HTML to Memos-flavoured Markdown.
+///
+/// Mentions become profile links, hashtags the policy allows become Memos tags (others stay
+/// plain text), `:shortcode:` custom emoji become images, and Markdown syntax in the text is
+/// only escaped where it would otherwise turn into formatting.
+pub fn to_markdown(html: &str, emojis: &[Emoji], tag_policy: &TagPolicy) -> String {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("div")),
+        vec![],
+    ).one(html);
+
+    let emojis = emoji_map(emojis);
+    let mut writer = Writer { out: String::new(), emojis: &emojis, tag_policy: Some(tag_policy) };
+    writer.children(&dom.document);
+    writer.out.trim().to_string()
+}
+
+/// Converts plain text (display names, content warnings) to Markdown, with custom emoji as images.
+pub fn plain_to_markdown(text: &str, emojis: &[Emoji]) -> String {
+    let emojis = emoji_map(emojis);
+    let mut writer = Writer { out: String::new(), emojis: &emojis, tag_policy: None };
+    writer.text(text);
+    writer.out
+}
+
+/// Quotes Markdown as a blockquote, prefixing every line so multi-line text stays inside it.
+pub fn blockquote(md: &str) -> String {
+    md.lines().map(|l| if l.is_empty() { ">".to_string() } else { format!("> {}", l) }).collect::<Vec<_>>().join("\n")
+}
+
+/// Flattens Memos Markdown into plain text for Mastodon: formatting markers, images and
+/// HTML are dropped, links become `text (url)`.
+pub fn to_plain_text(md: &str) -> String {
+    static RULES: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    let rules = RULES.get_or_init(|| {
+        [
+            (r"(?m)^```.*$\n?", ""),
+            (r"<[^>\n]+>", ""),
+            (r"\*\*([^*\n]+)\*\*|__([^_\n]+)__", "$1$2"),
+            (r"~~([^~\n]+)~~", "$1"),
+            (r"(^|[^\w*\\])\*([^*\n]+)\*", "$1$2"),
+            (r"`([^`\n]*)`", "$1"),
+            (r"(?m)^#{1,6}\s+", ""),
+            (r"\\([\\`*_{}\[\]()#+\-.!>~|])", "$1"),
+            (r"\n{3,}", "\n\n"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+        .collect()
+    });
+
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| Regex::new(r"(!?)\[([^\]]*)\]\(([^)\s]+)\)").unwrap());
+
+    let mut text = link.replace_all(md, |cap: &regex::Captures| {
+        match (&cap[1], &cap[2], &cap[3]) {
+            ("!", _, _) => String::new(),
+            (_, label, url) if label.is_empty() || label == url => url.to_string(),
+            (_, label, url) => format!("{} ({})", label, url),
+        }
+    }).into_owned();
+    for (re, replacement) in rules {
+        text = re.replace_all(&text, *replacement).into_owned();
+    }
+    text.trim().to_string()
+}
+
+fn emoji_map(emojis: &[Emoji]) -> HashMap<String, String> {
+    emojis.iter().map(|e| (e.shortcode.clone(), e.url.clone())).collect()
+}
+
+struct Writer<'a> {
+    out: String,
+    emojis: &'a HashMap<String, String>,
+    tag_policy: Option<&'a TagPolicy>,
+}
+
+impl<'a> Writer<'a> {
+    fn nested(&self) -> Writer<'a> {
+        Writer { out: String::new(), emojis: self.emojis, tag_policy: self.tag_policy }
+    }
+
+    fn children(&mut self, node: &Handle) {
+        for child in node.children.borrow().iter() {
+            self.node(child);
+        }
+    }
+
+    fn node(&mut self, node: &Handle) {
+        match &node.data {
+            NodeData::Text { contents } => {
+                let text = contents.borrow().replace('\n', " ");
+                self.text(&text);
+            }
+            NodeData::Element { name, attrs, .. } => {
+                let attr = |key: &str| attrs.borrow().iter()
+                    .find(|a| &*a.name.local == key)
+                    .map(|a| a.value.to_string())
+                    .unwrap_or_default();
+                match &*name.local {
+                    // Mastodon hides the scheme and tail of long URLs in these spans, and the
+                    // "RE: <url>" fallback of quote posts, which are rendered separately
+                    "span" | "p" if attr("class").split_whitespace().any(|c| c == "invisible" || c == "quote-inline") => {}
+                    "p" | "div" => {
+                        self.block_break();
+                        self.children(node);
+                        self.block_break();
+                    }
+                    "br" => self.out.push('\n'),
+                    "a" => self.link(node, &attr("href"), &attr("class"), &attr("rel")),
+                    "strong" | "b" => self.wrap(node, "**"),
+                    "em" | "i" => self.wrap(node, "*"),
+                    "del" | "s" => self.wrap(node, "~~"),
+                    "code" => {
+                        let code = text_content(node);
+                        if code.contains('`') {
+                            self.out.push_str(&format!("`` {} ``", code));
+                        } else {
+                            self.out.push_str(&format!("`{}`", code));
+                        }
+                    }
+                    "pre" => {
+                        self.block_break();
+                        self.out.push_str(&format!("```\n{}\n```", text_content(node).trim_end_matches('\n')));
+                        self.block_break();
+                    }
+                    "blockquote" => {
+                        let mut inner = self.nested();
+                        inner.children(node);
+                        self.block_break();
+                        let quoted: Vec<String> = inner.out.trim().lines().map(|l| format!("> {}", l).trim_end().to_string()).collect();
+                        self.out.push_str(&quoted.join("\n"));
+                        self.block_break();
+                    }
+                    "ul" | "ol" => {
+                        self.block_break();
+                        let ordered = &*name.local == "ol";
+                        let items = node.children.borrow().iter().filter(|c| is_element(c, "li")).cloned().collect::<Vec<_>>();
+                        for (i, item) in items.iter().enumerate() {
+                            let mut inner = self.nested();
+                            inner.children(item);
+                            let marker = if ordered { format!("{}. ", i + 1) } else { "- ".to_string() };
+                            let indent = " ".repeat(marker.len());
+                            for (n, line) in inner.out.trim().lines().enumerate() {
+                                self.out.push_str(if n == 0 { &marker } else { &indent });
+                                self.out.push_str(line);
+                                self.out.push('\n');
+                            }
+                        }
+                        self.block_break();
+                    }
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        self.block_break();
+                        let level = name.local.as_bytes()[1] - b'0';
+                        self.out.push_str(&format!("{} ", "#".repeat(level as usize)));
+                        self.children(node);
+                        self.block_break();
+                    }
+                    "img" | "script" | "style" => {}
+                    _ => self.children(node),
+                }
+            }
+            _ => self.children(node),
+        }
+    }
+
+    fn wrap(&mut self, node: &Handle, marker: &str) {
+        let mut inner = self.nested();
+        inner.children(node);
+        let text = inner.out.trim();
+        if !text.is_empty() {
+            self.out.push_str(&format!("{}{}{}", marker, text, marker));
+        }
+    }
+
+    fn link(&mut self, node: &Handle, href: &str, class: &str, rel: &str) {
+        let text = text_content(node);
+        let classes: Vec<&str> = class.split_whitespace().collect();
+
+        if classes.contains(&"mention") && text.starts_with('@') {
+            // Local mentions only show `@user`, so take the server from the profile URL
+            let handle = if text[1..].contains('@') {
+                text.clone()
+            } else {
+                let host = href.split("://").nth(1).and_then(|rest| rest.split('/').next()).unwrap_or_default();
+                format!("{}@{}", text, host)
+            };
+            self.out.push_str(&format!("[{}]({})", handle, destination(href)));
+        } else if classes.contains(&"hashtag") || rel.split_whitespace().any(|r| r == "tag") {
+            let name = text.trim_start_matches('#');
+            match tags::normalize(name).filter(|_| self.tag_policy.is_some_and(|p| p.allows(name))) {
+                Some(tag) => self.out.push_str(&format!("#{}", tag)),
+                None => self.text(&text),
+            }
+        } else if text.is_empty() || text == href {
+            self.out.push_str(href);
+        } else {
+            let mut inner = self.nested();
+            inner.text(&text);
+            self.out.push_str(&format!("[{}]({})", inner.out, destination(href)));
+        }
+    }
+
+    /// Ends the current block with a blank line (no-op at the start of the output).
+    fn block_break(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\n']).len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() {
+            self.out.push_str("\n\n");
+        }
+    }
+
+    /// Appends plain text, replacing custom emoji and escaping only what Markdown would misread.
+    fn text(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let prev = self.out.chars().last();
+            let next = chars.get(i + 1).copied();
+            let at_line_start = self.out.is_empty() || self.out.ends_with('\n');
+
+            if c == ':' {
+                if let Some(len) = chars[i + 1..].iter().position(|&ch| ch == ':') {
+                    let code: String = chars[i + 1..i + 1 + len].iter().collect();
+                    if let Some(url) = self.emojis.get(&code) {
+                        self.out.push_str(&format!("![:{}:]({})", code, destination(url)));
+                        i += len + 2;
+                        continue;
+                    }
+                }
+            }
+
+            let escape = match c {
+                '\\' => next.is_some_and(|n| n.is_ascii_punctuation()),
+                '*' | '`' | '[' | ']' => true,
+                // snake_case and ~ are harmless mid-word; only flanking runs format
+                '_' => !(prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric)),
+                '~' => next == Some('~') || prev == Some('~'),
+                // A bare #word would become a Memos tag, a leading "# " a heading
+                '#' => next.is_some_and(|n| n.is_alphanumeric()) || (at_line_start && matches!(next, Some(' ') | Some('#'))),
+                '>' => at_line_start,
+                '-' | '+' => at_line_start && next == Some(' '),
+                '.' | ')' => next == Some(' ') && is_list_number(&self.out),
+                _ => false,
+            };
+            if escape {
+                self.out.push('\\');
+            }
+            self.out.push(c);
+            i += 1;
+        }
+    }
+}
+
+/// A URL made safe as a Markdown link destination: parentheses, spaces and angle brackets
+/// would end the link early, so they are percent-encoded.
+pub fn destination(url: &str) -> String {
+    let mut out = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            '(' => out.push_str("%28"),
+            ')' => out.push_str("%29"),
+            ' ' => out.push_str("%20"),
+            '<' => out.push_str("%3C"),
+            '>' => out.push_str("%3E"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// True if the current line so far is just a number, so a following ". " would start a list.
+fn is_list_number(out: &str) -> bool {
+    let line = out.rsplit('\n').next().unwrap_or_default();
+    !line.is_empty() && line.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_element(node: &Handle, tag: &str) -> bool {
+    matches!(&node.data, NodeData::Element { name, .. } if &*name.local == tag)
+}
+
+/// Raw text of a node (including Mastodon's hidden URL fragments), with `<br>` as newlines.
+fn text_content(node: &Handle) -> String {
+    let mut text = String::new();
+    for child in node.children.borrow().iter() {
+        match &child.data {
+            NodeData::Text { contents } => text.push_str(&contents.borrow()),
+            NodeData::Element { name, .. } if &*name.local == "br" => text.push('\n'),
+            NodeData::Element { .. } => text.push_str(&text_content(child)),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        let policy = TagPolicy::from_env("https://example.social", &[]).unwrap();
+        to_markdown(html, &[], &policy)
+    }
+
+    #[test]
+    fn link_destinations_with_parentheses_stay_intact() {
+        let md = convert(r#"<p>See <a href="https://en.wikipedia.org/wiki/Rust_(programming_language)">the article</a></p>"#);
+        assert_eq!(md, "See [the article](https://en.wikipedia.org/wiki/Rust_%28programming_language%29)");
+        assert_eq!(to_plain_text(&md), "See the article (https://en.wikipedia.org/wiki/Rust_%28programming_language%29)");
+    }
+
+    #[test]
+    fn mentions_get_the_server_of_local_accounts() {
+        let md = convert(r#"<p><span class="h-card"><a href="https://example.social/@jane" class="u-url mention">@<span>jane</span></a></span> hi</p>"#);
+        assert_eq!(md, "[@jane@example.social](https://example.social/@jane) hi");
+    }
+
+    #[test]
+    fn shortened_urls_are_written_out_in_full() {
+        let md = convert(r#"<p><a href="https://example.com/a/very/long/path"><span class="invisible">https://</span><span class="ellipsis">example.com/a/very</span><span class="invisible">/long/path</span></a></p>"#);
+        assert_eq!(md, "https://example.com/a/very/long/path");
+    }
+
+    #[test]
+    fn only_markdown_that_would_format_is_escaped() {
+        assert_eq!(plain_to_markdown("snake_case and *stars*", &[]), r"snake_case and \*stars\*");
+        assert_eq!(plain_to_markdown("# heading and #tag", &[]), r"\# heading and \#tag");
+        assert_eq!(plain_to_markdown("1. not a list", &[]), r"1\. not a list");
+    }
+
+    #[test]
+    fn custom_emoji_become_images() {
+        let emojis = [Emoji { shortcode: "blobcat".into(), url: "https://example.social/emoji/blobcat.png".into(), static_url: String::new() }];
+        assert_eq!(plain_to_markdown("hi :blobcat: :nope:", &emojis), "hi ![:blobcat:](https://example.social/emoji/blobcat.png) :nope:");
+    }
+
+    #[test]
+    fn blockquotes_cover_every_line() {
+        assert_eq!(blockquote("one\ntwo\n\nthree"), "> one\n> two\n>\n> three");
+    }
+
+    #[test]
+    fn plain_text_drops_formatting() {
+        assert_eq!(to_plain_text("**bold** and *em* and `code`\n\n![img](https://x.y/a.png)\n\n\n\n[x.y](x.y)"), "bold and em and code\n\nx.y");
+    }
+}
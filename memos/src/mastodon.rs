@@ -0,0 +1,3 @@
+//! The Mastodon API client shared by every bot in this repository.
+
+pub use mastodon_client::{is_gone, is_rate_limited, Account, Attachment, Client as Mastodon, Conversation, Emoji, NewStatus, Poll, PollOption, Status};
@@ -0,0 +1,162 @@
+use serde_json::{json, Value};
+
+use crate::markdown;
+use crate::mastodon::Attachment;
+
+/// `image`, `video`, `gifv`, `audio` or `unknown`. ActivityPub attachments carry a MIME type
+/// instead, which is mapped onto the same kinds.
+pub fn kind(attachment: &Attachment) -> &str {
+    match attachment.kind.as_str() {
+        mime if mime.starts_with("image/") => "image",
+        mime if mime.starts_with("video/") => "video",
+        mime if mime.starts_with("audio/") => "audio",
+        kind @ ("image" | "video" | "gifv" | "audio") => kind,
+        _ => "unknown",
+    }
+}
+
+/// Where the file can be fetched from, best first: our server's cached copy, then the
+/// original on the author's server (remote posts whose cache was pruned have no `url`).
+pub fn sources(attachment: &Attachment) -> Vec<&str> {
+    let mut urls: Vec<&str> = Vec::new();
+    for url in [&attachment.url, &attachment.remote_url].into_iter().filter_map(|u| u.as_deref()) {
+        if !url.is_empty() && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// An attachment for the memo template. `linked` is the URL the memo points at, or `None`
+/// when the file was copied into Memos; then only the alt text is left to show, so
+/// attachments without one are skipped.
+pub fn context(attachment: &Attachment, linked: Option<&str>) -> Option<Value> {
+    let description = alt_text(attachment);
+    let embed = match linked {
+        Some(url) => embed(attachment, url),
+        None => format!("*Alt text:* {}", caption(description?)),
+    };
+    Some(json!({
+        "url": linked,
+        "type": kind(attachment),
+        "preview": attachment.preview_url,
+        "description": description,
+        "embed": embed,
+    }))
+}
+
+/// Markdown for a linked attachment: images inline, videos as a clickable thumbnail, audio
+/// and other files as links, each followed by its alt text.
+pub fn embed(attachment: &Attachment, url: &str) -> String {
+    let description = alt_text(attachment);
+    let alt = description.map(|d| d.replace(['[', ']', '\n', '\r'], " ")).unwrap_or_default();
+    let preview = attachment.preview_url.as_deref().filter(|p| !p.is_empty() && *p != url).map(markdown::destination);
+    let url = &markdown::destination(url);
+
+    let mut text = match kind(attachment) {
+        "image" => format!("![{}]({})", alt, url),
+        kind @ ("video" | "gifv") => {
+            let label = if kind == "gifv" { "🎞️ [GIF]" } else { "🎬 [Video]" };
+            match preview {
+                Some(preview) => format!("[![{}]({})]({})\n{}({})", alt, preview, url, label, url),
+                None => format!("{}({})", label, url),
+            }
+        }
+        "audio" => format!("🎵 [Audio]({})", url),
+        _ => format!("📎 [File]({})", url),
+    };
+    if let Some(description) = description {
+        text.push_str(&format!("\n*{}*", caption(description)));
+    }
+    text
+}
+
+fn alt_text(attachment: &Attachment) -> Option<&str> {
+    attachment.description.as_deref().map(str::trim).filter(|d| !d.is_empty())
+}
+
+/// Alt text as a single line of escaped Markdown.
+fn caption(description: &str) -> String {
+    let line = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    markdown::plain_to_markdown(&line, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(kind: &str, url: Option<&str>, preview: Option<&str>, description: Option<&str>) -> Attachment {
+        Attachment {
+            kind: kind.into(),
+            url: url.map(str::to_string),
+            preview_url: preview.map(str::to_string),
+            description: description.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn kind_of(kind: &str) -> String {
+        super::kind(&attachment(kind, None, None, None)).to_string()
+    }
+
+    #[test]
+    fn kinds_cover_api_types_and_mime_types() {
+        for (kind, expected) in [
+            ("image", "image"),
+            ("gifv", "gifv"),
+            ("video", "video"),
+            ("audio", "audio"),
+            ("unknown", "unknown"),
+            ("", "unknown"),
+            ("image/png", "image"),
+            ("video/mp4", "video"),
+            ("audio/ogg", "audio"),
+            ("application/pdf", "unknown"),
+        ] {
+            assert_eq!(kind_of(kind), expected, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn embeds_per_kind() {
+        let url = "https://files.example/a";
+        let preview = Some("https://files.example/a-small.png");
+        assert_eq!(embed(&attachment("image", Some(url), preview, None), url), "![](https://files.example/a)");
+        assert_eq!(
+            embed(&attachment("video", Some(url), preview, None), url),
+            "[![](https://files.example/a-small.png)](https://files.example/a)\n🎬 [Video](https://files.example/a)",
+        );
+        assert_eq!(embed(&attachment("gifv", Some(url), None, None), url), "🎞️ [GIF](https://files.example/a)");
+        assert_eq!(embed(&attachment("audio", Some(url), preview, None), url), "🎵 [Audio](https://files.example/a)");
+        assert_eq!(embed(&attachment("unknown", Some(url), None, None), url), "📎 [File](https://files.example/a)");
+    }
+
+    #[test]
+    fn remote_url_is_the_fallback_source() {
+        let mut pruned = attachment("image", None, None, None);
+        pruned.remote_url = Some("https://origin.example/a.png".into());
+        assert_eq!(sources(&pruned), vec!["https://origin.example/a.png"]);
+
+        let mut cached = attachment("image", Some("https://cache.example/a.png"), None, None);
+        cached.remote_url = Some("https://origin.example/a.png".into());
+        assert_eq!(sources(&cached), vec!["https://cache.example/a.png", "https://origin.example/a.png"]);
+
+        cached.remote_url = cached.url.clone();
+        assert_eq!(sources(&cached), vec!["https://cache.example/a.png"]);
+        assert!(sources(&attachment("image", Some(""), None, None)).is_empty());
+    }
+
+    #[test]
+    fn alt_text_cant_break_the_markdown() {
+        let url = "https://files.example/a.png";
+        let tricky = attachment("image", Some(url), None, Some("a [cat](x) *sleeping*\non a\r\nmat"));
+        let md = embed(&tricky, url);
+        let (image, caption) = md.split_once('\n').unwrap();
+        assert_eq!(image, "![a  cat (x) *sleeping* on a  mat](https://files.example/a.png)");
+        assert_eq!(caption, r"*a \[cat\](x) \*sleeping\* on a mat*");
+
+        let stored = context(&tricky, None).unwrap();
+        assert!(!stored["embed"].as_str().unwrap().contains('\n'));
+        assert!(context(&attachment("image", Some(url), None, Some("  ")), None).is_none());
+    }
+}
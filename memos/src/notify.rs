@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::mastodon::{Mastodon, NewStatus, Status};
+use crate::state::SyncState;
+
+/// Longest error text quoted in a notification, so it fits in a status.
+const MAX_ERROR_CHARS: usize = 300;
+
+/// How failures are reported on Mastodon (MEMOS_NOTIFY).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyMode {
+    /// Only the local failure log
+    Off,
+    /// A private reply to the trigger post (only visible to this account)
+    Reply,
+    /// A private status linking to the trigger post
+    Dm,
+}
+
+/// Reports captures that couldn't be saved, so a stuck trigger doesn't go unnoticed.
+///
+/// Every failure is appended to the failure log; Mastodon is told once per trigger, until
+/// it is saved. The trigger itself is never touched.
+pub struct Notifier {
+    mode: NotifyMode,
+    /// MEMOS_FAILURE_LOG, default `failures.log` (empty disables it)
+    log: Option<PathBuf>,
+}
+
+impl Notifier {
+    /// Reads MEMOS_NOTIFY (`reply` by default, `dm` or `off`) and MEMOS_FAILURE_LOG.
+    pub fn from_env() -> Result<Self> {
+        let mode = match std::env::var("MEMOS_NOTIFY").unwrap_or_default().to_lowercase().as_str() {
+            "" | "reply" => NotifyMode::Reply,
+            "dm" | "direct" => NotifyMode::Dm,
+            "off" | "false" | "none" => NotifyMode::Off,
+            other => bail!("Unknown MEMOS_NOTIFY '{}' (expected reply, dm or off)", other),
+        };
+        let log = match std::env::var("MEMOS_FAILURE_LOG") {
+            Ok(path) if path.is_empty() => None,
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from("failures.log")),
+        };
+        Ok(Notifier { mode, log })
+    }
+
+    /// Records that `status` (a trigger) couldn't be handled; `what` says what went wrong and
+    /// what happens next.
+    pub async fn failure(&self, mastodon: &Mastodon, state: &mut SyncState, status: &Status, what: &str, error: &anyhow::Error) {
+        let status_id = status.id.as_str();
+        let url = status.url.as_deref().unwrap_or_default();
+        self.log_line(status_id, url, what, error);
+
+        if self.mode == NotifyMode::Off || state.notified.contains(status_id) {
+            return;
+        }
+        // Keep the error from turning into hashtags (and new triggers) or mentions
+        let detail: String = format!("{:#}", error).replace(['#', '@'], "").chars().take(MAX_ERROR_CHARS).collect();
+        let report = match self.mode {
+            NotifyMode::Reply => NewStatus::new(format!("⚠️ {}: {}", what, detail)).in_reply_to(status_id),
+            _ => NewStatus::new(format!("⚠️ {} {}: {}", what, url, detail)),
+        };
+        match mastodon.post_status(&report.visibility("direct")).await {
+            Ok(report) => {
+                info!(status_id = %status_id, "📣 Failure reported on Mastodon");
+                state.notified.insert(status_id.to_string());
+                state.bot_replies.insert(report.id);
+            }
+            Err(e) => warn!(status_id = %status_id, error = %e, "⚠️ Couldn't report the failure on Mastodon"),
+        }
+    }
+
+    /// Forgets a reported failure once the trigger is saved, so a later failure is reported again.
+    pub fn resolved(&self, state: &mut SyncState, status_id: &str) {
+        state.notified.remove(status_id);
+    }
+
+    fn log_line(&self, status_id: &str, url: &str, what: &str, error: &anyhow::Error) {
+        let Some(path) = &self.log else { return };
+        let error = format!("{:#}", error).replace(['\n', '\r', '\t'], " ");
+        let line = format!("{}\t{}\t{}\t{}: {}\n", chrono::Utc::now().to_rfc3339(), status_id, url, what, error);
+        let written = OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(e) = written {
+            warn!(path = %path.display(), error = %e, "⚠️ Couldn't write the failure log");
+        }
+    }
+}
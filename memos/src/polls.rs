@@ -0,0 +1,188 @@
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use tracing::{error, info, warn};
+use bot_support::health::Metrics;
+
+use crate::markdown;
+use crate::mastodon::{self, Mastodon, Status};
+use crate::sink::Sink;
+use crate::state::{PendingPoll, SyncState};
+
+/// Give up on a poll whose results never come in this long after it should have closed.
+const GIVE_UP_AFTER_DAYS: i64 = 7;
+
+/// The poll of a status for the memo template, or `None` if it has none.
+///
+/// Percentages are of voters for multiple-choice polls (as Mastodon shows them) and of
+/// votes otherwise.
+pub fn context(status: &Status) -> Option<Value> {
+    let poll = status.poll.as_ref()?;
+    let votes = poll.votes_count;
+    let voters = poll.voters_count.unwrap_or(votes);
+    let total = if poll.multiple { voters } else { votes };
+
+    let options: Vec<Value> = poll.options.iter()
+        .map(|opt| {
+            let count = opt.votes_count.unwrap_or(0);
+            let percent = if total == 0 { 0 } else { (count as f64 * 100.0 / total as f64).round() as u64 };
+            json!({
+                "title": markdown::plain_to_markdown(&opt.title, &status.emojis),
+                "votes": count,
+                "percent": percent,
+            })
+        })
+        .collect();
+    let expires_at = poll.expires_at.as_deref()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| at.with_timezone(&Utc).format("%Y-%m-%d %H:%M UTC").to_string());
+
+    Some(json!({
+        "options": options,
+        "votes": votes,
+        "voters": voters,
+        "multiple": poll.multiple,
+        "expired": poll.expired,
+        "expires_at": expires_at,
+    }))
+}
+
+/// The final results appended to a memo once its poll has closed.
+fn results_section(poll: &Value) -> String {
+    let mut text = format!("\n\n---\n\n📊 **Final poll results** ({} voters):", poll["voters"]);
+    for opt in poll["options"].as_array().into_iter().flatten() {
+        text.push_str(&format!("\n* {}: {} votes ({}%)", opt["title"].as_str().unwrap_or_default(), opt["votes"], opt["percent"]));
+    }
+    text
+}
+
+/// Appends the final results to memos whose poll was still open when they were saved.
+pub struct PollResults;
+
+impl PollResults {
+    /// Reads MEMOS_POLL_RESULTS (off by default).
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("MEMOS_POLL_RESULTS").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        Some(PollResults)
+    }
+
+    /// Remembers `status`, saved as `memo`, if it has a poll that hasn't closed yet.
+    pub fn track(&self, state: &mut SyncState, status: &Status, memo: &str) {
+        let Some(poll) = status.poll.as_ref().filter(|p| !p.expired) else {
+            return;
+        };
+        let Some(expires_at) = poll.expires_at.as_ref().filter(|_| !status.id.is_empty()) else {
+            return;
+        };
+        state.polls.insert(status.id.clone(), PendingPoll { memo: memo.to_string(), expires_at: expires_at.clone() });
+    }
+
+    /// Re-fetches every tracked poll past its end and appends the results to its memo.
+    /// Returns whether the list changed.
+    pub async fn check(&self, mastodon: &Mastodon, sink: &Sink, state: &mut SyncState, metrics: &Metrics) -> bool {
+        let now = Utc::now();
+        let due: Vec<String> = state.polls.iter()
+            .filter(|(_, p)| DateTime::parse_from_rfc3339(&p.expires_at).map_or(true, |at| at <= now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut changed = false;
+
+        for id in due {
+            let pending = state.polls[&id].clone();
+            let status = match mastodon.status(&id).await {
+                Ok(status) => status,
+                Err(e) if mastodon::is_gone(&e) => {
+                    info!(status_id = %id, "🗑️ Poll was deleted before it closed");
+                    state.polls.remove(&id);
+                    changed = true;
+                    continue;
+                }
+                Err(e) => {
+                    warn!(status_id = %id, error = %e, "⚠️ Couldn't fetch the poll results, trying again next cycle");
+                    if mastodon::is_rate_limited(&e) {
+                        metrics.rate_limited();
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            let poll = context(&status).filter(|p| p["expired"] == true);
+            let Some(poll) = poll else {
+                // Servers close polls a little after `expires_at`, so wait unless it's been days
+                let overdue = DateTime::parse_from_rfc3339(&pending.expires_at)
+                    .map_or(true, |at| at + Duration::days(GIVE_UP_AFTER_DAYS) < now);
+                if overdue {
+                    warn!(status_id = %id, memo = %pending.memo, "⚠️ Poll never closed, no longer waiting for its results");
+                    state.polls.remove(&id);
+                    changed = true;
+                }
+                continue;
+            };
+            match sink.append_to_memo(&pending.memo, &results_section(&poll)).await {
+                Ok(()) => {
+                    info!(status_id = %id, memo = %pending.memo, "📊 Poll closed, final results added to the memo");
+                    state.polls.remove(&id);
+                    changed = true;
+                }
+                // A deleted memo can't take the results, so stop trying
+                Err(_) if matches!(sink.memo_exists(&pending.memo).await, Ok(false)) => {
+                    info!(status_id = %id, memo = %pending.memo, "🗑️ Memo was deleted, dropping its poll results");
+                    state.polls.remove(&id);
+                    changed = true;
+                }
+                Err(e) => {
+                    error!(status_id = %id, memo = %pending.memo, error = %e, "❌ Couldn't add the poll results to the memo");
+                    metrics.failure(e);
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mastodon::{Poll, PollOption};
+
+    fn status(multiple: bool, votes: u64, voters: Option<u64>, counts: &[Option<u64>]) -> Status {
+        let options = counts.iter().enumerate()
+            .map(|(i, &votes_count)| PollOption { title: format!("option {}", i + 1), votes_count })
+            .collect();
+        let poll = Poll { multiple, votes_count: votes, voters_count: voters, options, ..Default::default() };
+        Status { poll: Some(poll), ..Default::default() }
+    }
+
+    fn percents(poll: &Value) -> Vec<u64> {
+        poll["options"].as_array().unwrap().iter().map(|o| o["percent"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn single_choice_is_a_share_of_votes() {
+        let poll = context(&status(false, 3, None, &[Some(2), Some(1)])).unwrap();
+        assert_eq!(percents(&poll), vec![67, 33]);
+        assert_eq!(poll["voters"], 3);
+    }
+
+    #[test]
+    fn multiple_choice_is_a_share_of_voters() {
+        let poll = context(&status(true, 5, Some(4), &[Some(3), Some(2)])).unwrap();
+        assert_eq!(percents(&poll), vec![75, 50]);
+        assert_eq!(poll["votes"], 5);
+        assert_eq!(poll["voters"], 4);
+    }
+
+    #[test]
+    fn no_votes_and_hidden_counts_are_zero() {
+        let poll = context(&status(true, 0, Some(0), &[Some(0), None])).unwrap();
+        assert_eq!(percents(&poll), vec![0, 0]);
+        assert_eq!(poll["options"][1]["votes"], 0);
+    }
+
+    #[test]
+    fn statuses_without_a_poll_have_none() {
+        assert!(context(&Status::default()).is_none());
+    }
+}
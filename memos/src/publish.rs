@@ -0,0 +1,210 @@
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+use bot_support::health::Metrics;
+
+use crate::markdown;
+use crate::mastodon::{Mastodon, NewStatus};
+use crate::memos_api::{Memo, MemosClient};
+use crate::state::SyncState;
+
+/// How many of the newest memos are checked for publishing each cycle.
+const PUBLISH_SCAN: usize = 50;
+/// Mastodon's attachment limit per status.
+const MAX_MEDIA: usize = 4;
+/// Room kept free in each thread part for the " (1/3)" counter.
+const COUNTER_RESERVE: usize = 8;
+
+/// Posts selected memos to Mastodon (the reverse direction of the sync).
+pub struct Publisher {
+    /// Memos carrying this tag are published (MEMOS_PUBLISH_TAG, default `toot`)
+    tag: Regex,
+    /// Also publish every PUBLIC memo (MEMOS_PUBLISH_PUBLIC)
+    public: bool,
+    /// Mastodon visibility for published statuses (MEMOS_PUBLISH_VISIBILITY)
+    visibility: String,
+    /// The server's status length limit
+    max_chars: usize,
+}
+
+impl Publisher {
+    /// Reads MEMOS_PUBLISH (off by default) and asks the server for its character limit.
+    pub async fn from_env(mastodon: &Mastodon) -> Result<Option<Self>> {
+        if std::env::var("MEMOS_PUBLISH").map(|v| v != "true").unwrap_or(true) {
+            return Ok(None);
+        }
+        let tag = std::env::var("MEMOS_PUBLISH_TAG").unwrap_or_else(|_| "toot".into());
+        let tag = Regex::new(&format!(r"(?i)(^|\s)#{}\b", regex::escape(tag.trim_start_matches('#'))))?;
+        let public = std::env::var("MEMOS_PUBLISH_PUBLIC").map(|v| v == "true").unwrap_or(false);
+        let visibility = std::env::var("MEMOS_PUBLISH_VISIBILITY").unwrap_or_else(|_| "public".into());
+
+        let max_chars = match mastodon.instance().await {
+            Ok(instance) => instance.configuration.statuses.max_characters,
+            Err(e) => {
+                warn!(error = %e, "⚠️ Couldn't read the server's character limit, assuming 500");
+                500
+            }
+        };
+        Ok(Some(Publisher { tag, public, visibility, max_chars }))
+    }
+
+    /// True for memos the user wants on Mastodon. Memos this bot saved from Mastodon never
+    /// qualify, whatever tags they carry.
+    fn wants(&self, memo: &Memo, captured: &HashSet<&str>) -> bool {
+        if captured.contains(memo.name.as_str()) {
+            return false;
+        }
+        self.tag.is_match(&memo.content) || (self.public && memo.visibility == "PUBLIC")
+    }
+
+    /// Publishes every wanted memo that hasn't been published yet, oldest first.
+    pub async fn run(&self, memos: &MemosClient, mastodon: &Mastodon, state: &mut SyncState, metrics: &Metrics) {
+        let listed = match memos.list_memos(PUBLISH_SCAN).await {
+            Ok(listed) => listed,
+            Err(e) => {
+                error!(error = %e, "❌ Couldn't list memos to publish");
+                metrics.failure(e);
+                return;
+            }
+        };
+        let captured = state.captured_memos();
+        let wanted: Vec<&Memo> = listed.iter().rev().filter(|m| self.wants(m, &captured)).collect();
+
+        // Memos that already qualified when publishing was switched on are not posted retroactively
+        if !state.publish_seeded {
+            for memo in &wanted {
+                state.published.insert(memo.name.clone(), String::new());
+            }
+            state.publish_seeded = true;
+            info!(skipped = wanted.len(), "📤 Publishing enabled, existing memos left as they are");
+            return;
+        }
+
+        for memo in wanted {
+            if state.published.contains_key(&memo.name) {
+                continue;
+            }
+            match self.publish(memo, memos, mastodon, metrics).await {
+                Ok(url) => {
+                    info!(memo = %memo.name, url = %url, "📤 Memo published to Mastodon");
+                    metrics.post_ok();
+                    if let Err(e) = memos.append_to_memo(&memo.name, &format!("\n\n🐘 {}", url)).await {
+                        warn!(memo = %memo.name, error = %e, "⚠️ Published, but couldn't add the link to the memo");
+                    }
+                    state.published.insert(memo.name.clone(), url);
+                }
+                Err(e) => {
+                    error!(memo = %memo.name, error = %e, "❌ Couldn't publish the memo");
+                    metrics.failure(e);
+                }
+            }
+        }
+    }
+
+    /// Posts a memo as a status (or a self-reply thread if it's too long) and returns the
+    /// URL of the first status.
+    async fn publish(&self, memo: &Memo, memos: &MemosClient, mastodon: &Mastodon, metrics: &Metrics) -> Result<String> {
+        let content = self.tag.replace_all(&memo.content, "$1");
+        let parts = split_thread(&markdown::to_plain_text(&content), self.max_chars);
+
+        let mut media_ids = Vec::new();
+        for resource in memo.resources.iter().take(MAX_MEDIA) {
+            let bytes = memos.download_resource(resource).await?;
+            media_ids.push(mastodon.upload_media(bytes, &resource.filename, &resource.mime, None).await?.id);
+            metrics.media_uploaded();
+        }
+
+        let mut first_url = None;
+        let mut reply_to: Option<String> = None;
+        for part in parts {
+            let post = NewStatus::new(part).visibility(&self.visibility);
+            let post = match &reply_to {
+                Some(id) => post.in_reply_to(id),
+                None => post.media(media_ids.clone()),
+            };
+            let status = match mastodon.post_status(&post).await {
+                Ok(status) => status,
+                // The head of the thread is out; report it as published so it isn't posted twice
+                Err(e) if first_url.is_some() => {
+                    error!(memo = %memo.name, error = %e, "❌ Thread cut short");
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            if first_url.is_none() {
+                first_url = status.url;
+            }
+            reply_to = Some(status.id);
+        }
+        Ok(first_url.unwrap_or_default())
+    }
+}
+
+/// Splits text into parts that fit `max_chars`, breaking at paragraphs, then words, and
+/// numbering the parts when there is more than one.
+fn split_thread(text: &str, max_chars: usize) -> Vec<String> {
+    if text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+    let limit = max_chars.saturating_sub(COUNTER_RESERVE).max(1);
+
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n") {
+        for (i, word) in paragraph.split(' ').enumerate() {
+            let separator = match (current.is_empty(), i) {
+                (true, _) => "",
+                (false, 0) => "\n\n",
+                _ => " ",
+            };
+            if current.chars().count() + separator.len() + word.chars().count() > limit && !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str(separator);
+            }
+            // A single word longer than a whole status gets cut
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > limit {
+                parts.push(word.drain(..limit).collect());
+            }
+            current.extend(word);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    let total = parts.len();
+    parts.into_iter().enumerate().map(|(i, part)| format!("{} ({}/{})", part.trim(), i + 1, total)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_a_single_unnumbered_post() {
+        assert_eq!(split_thread("hello world", 500), vec!["hello world"]);
+    }
+
+    #[test]
+    fn long_text_breaks_at_paragraphs_then_words() {
+        let text = format!("{}\n\n{}", "a".repeat(20), "word ".repeat(10).trim());
+        let parts = split_thread(&text, 30);
+        assert_eq!(parts, vec![
+            format!("{} (1/4)", "a".repeat(20)),
+            "word word word word (2/4)".to_string(),
+            "word word word word (3/4)".to_string(),
+            "word word (4/4)".to_string(),
+        ]);
+        assert!(parts.iter().all(|p| p.chars().count() <= 30));
+    }
+
+    #[test]
+    fn overlong_words_are_cut() {
+        let parts = split_thread(&"x".repeat(25), 18);
+        assert_eq!(parts, vec![format!("{} (1/3)", "x".repeat(10)), format!("{} (2/3)", "x".repeat(10)), "xxxxx (3/3)".to_string()]);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use tracing::{info, warn};
+
+use crate::mastodon::Status;
+use crate::memos_api::MemosClient;
+use crate::vault::Vault;
+
+/// Where captured posts are stored (MEMOS_SINK).
+///
+/// Both backends call what they store a "memo" and identify it by a string: a Memos memo
+/// name or ID, or a note's file name in the vault.
+pub enum Sink {
+    /// A Memos server (default)
+    Memos(MemosClient),
+    /// A local folder of Markdown notes
+    Vault(Vault),
+}
+
+impl Sink {
+    /// Reads MEMOS_SINK: `memos` (default, needs MEMOS_URL and MEMOS_TOKEN) or `vault`
+    /// (needs MEMOS_VAULT_DIR). `memos_version` skips detecting the server's version.
+    pub async fn from_env(http: Client, memos_version: Option<&str>) -> Result<Self> {
+        match std::env::var("MEMOS_SINK").unwrap_or_default().to_lowercase().as_str() {
+            "" | "memos" => {
+                let url = std::env::var("MEMOS_URL").context("MEMOS_URL missing")?;
+                let token = std::env::var("MEMOS_TOKEN").context("MEMOS_TOKEN missing")?;
+                let memos = MemosClient::connect(http, &url, &token, memos_version).await?;
+                info!(generation = ?memos.generation(), "📒 Connected to Memos");
+                Ok(Sink::Memos(memos))
+            }
+            "vault" | "markdown" => {
+                let dir = std::env::var("MEMOS_VAULT_DIR").context("MEMOS_VAULT_DIR missing")?;
+                let vault = Vault::new(http, &dir)?;
+                info!(dir = %vault.dir().display(), "📁 Saving to a Markdown vault");
+                Ok(Sink::Vault(vault))
+            }
+            other => bail!("Unknown MEMOS_SINK '{}' (expected memos or vault)", other),
+        }
+    }
+
+    /// The Memos server, for features that only exist there (publishing).
+    pub fn memos(&self) -> Option<&MemosClient> {
+        match self {
+            Sink::Memos(memos) => Some(memos),
+            Sink::Vault(_) => None,
+        }
+    }
+
+    /// Stores a memo for `source` (the status it was saved from) with the copied media attached.
+    ///
+    /// If the memo can't be stored, the media is deleted again, so a retry doesn't leave
+    /// orphaned copies behind.
+    pub async fn create_memo(&self, content: &str, visibility: &str, resources: &[String], source: &Status) -> Result<String> {
+        let created = match self {
+            Sink::Memos(memos) => memos.create_memo(content, visibility, resources).await,
+            Sink::Vault(vault) => vault.create_note(content, resources, source),
+        };
+        if created.is_err() {
+            self.delete_resources(resources).await;
+        }
+        created
+    }
+
+    /// Best-effort removal of uploaded media that no memo uses.
+    pub async fn delete_resources(&self, resources: &[String]) {
+        for resource in resources {
+            let deleted = match self {
+                Sink::Memos(memos) => memos.delete_resource(resource).await,
+                Sink::Vault(vault) => vault.delete_file(resource),
+            };
+            if let Err(e) = deleted {
+                warn!(resource = %resource, error = %e, "⚠️ Couldn't delete unused media");
+            }
+        }
+    }
+
+    pub async fn memo_exists(&self, memo: &str) -> Result<bool> {
+        match self {
+            Sink::Memos(memos) => memos.memo_exists(memo).await,
+            Sink::Vault(vault) => Ok(vault.note_exists(memo)),
+        }
+    }
+
+    pub async fn append_to_memo(&self, memo: &str, text: &str) -> Result<()> {
+        match self {
+            Sink::Memos(memos) => memos.append_to_memo(memo, text).await,
+            Sink::Vault(vault) => vault.append(memo, text),
+        }
+    }
+
+    pub async fn link_memos(&self, memo: &str, related: &str) -> Result<()> {
+        match self {
+            Sink::Memos(memos) => memos.link_memos(memo, related).await,
+            Sink::Vault(vault) => vault.link(memo, related),
+        }
+    }
+
+    /// Backdates a memo. Vault notes already carry the post's date in their front matter.
+    pub async fn set_create_time(&self, memo: &str, created_at: &str) -> Result<()> {
+        match self {
+            Sink::Memos(memos) => memos.set_create_time(memo, created_at).await,
+            Sink::Vault(_) => Ok(()),
+        }
+    }
+
+    pub async fn upload_resource(&self, filename: &str, mime: &str, bytes: &[u8]) -> Result<String> {
+        match self {
+            Sink::Memos(memos) => memos.upload_resource(filename, mime, bytes).await,
+            Sink::Vault(vault) => vault.store_file(filename, bytes),
+        }
+    }
+
+    pub async fn copy_remote_file(&self, url: &str) -> Result<String> {
+        match self {
+            Sink::Memos(memos) => memos.copy_remote_file(url).await,
+            Sink::Vault(vault) => vault.copy_remote_file(url).await,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use bot_support::store;
+
+/// Local record of what has already been saved, so triggers are never synced twice.
+const STATE_FILE: &str = "synced.json";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SyncState {
+    /// Trigger status ID -> memo it was saved as
+    #[serde(default)]
+    pub synced: HashMap<String, String>,
+    /// Newest status already scanned on our own timeline
+    #[serde(default)]
+    pub since_id: Option<String>,
+    /// Saved posts from others that are re-checked for edits and deletions, by status ID
+    #[serde(default)]
+    pub watched: HashMap<String, Watched>,
+    /// Memo -> URL of the Mastodon status it was published as (empty for memos skipped when
+    /// publishing was first enabled)
+    #[serde(default)]
+    pub published: HashMap<String, String>,
+    /// Whether memos that existed before publishing was enabled have been recorded
+    #[serde(default)]
+    pub publish_seeded: bool,
+    /// Triggers whose failure was already reported on Mastodon, by status ID
+    #[serde(default)]
+    pub notified: HashSet<String>,
+    /// Statuses this bot posted under captured posts (failure reports, acknowledgements),
+    /// kept out of saved threads
+    #[serde(default)]
+    pub bot_replies: HashSet<String>,
+    /// Linked-thread memos by trigger status ID, then by post (status ID or URL), so a retry
+    /// after a partial failure reuses the memos already created
+    #[serde(default)]
+    pub thread_memos: HashMap<String, HashMap<String, String>>,
+    /// Saved polls still waiting for their final results, by status ID
+    #[serde(default)]
+    pub polls: HashMap<String, PendingPoll>,
+}
+
+/// A memo that mirrors someone else's status.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Watched {
+    pub memo: String,
+    /// `edited_at` of the version last written to the memo
+    pub edited_at: Option<String>,
+    /// Unix time the memo was created, to stop watching after MEMOS_WATCH_DAYS
+    pub saved_at: u64,
+}
+
+/// A memo whose poll was still open when it was saved.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingPoll {
+    pub memo: String,
+    /// When the poll closes (RFC 3339)
+    pub expires_at: String,
+}
+
+impl SyncState {
+    /// Loads the state from local storage, starting fresh if there is none.
+    ///
+    /// An unreadable file is an error: starting fresh would capture (and clean up) every
+    /// trigger again.
+    pub fn load() -> Result<Self> {
+        let state = store::load(STATE_FILE)
+            .with_context(|| format!("Couldn't load {}; fix or remove it to start over", STATE_FILE))?;
+        Ok(state.unwrap_or_default())
+    }
+
+    /// Every memo saved from Mastodon, so publishing never sends one back.
+    pub fn captured_memos(&self) -> HashSet<&str> {
+        self.synced.values()
+            .chain(self.thread_memos.values().flat_map(|memos| memos.values()))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Persists the state, replacing the file atomically.
+    pub fn save(&self) -> Result<()> {
+        store::save(STATE_FILE, self).with_context(|| format!("Couldn't save {}", STATE_FILE))
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{bail, Result};
+
+use crate::mastodon::{Account, Status};
+use crate::triggers::{Trigger, TriggerKind};
+
+/// How the author shows up in the tag line (MEMOS_AUTHOR_TAG).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthorTag {
+    /// `#gargron`, short but ambiguous across instances
+    Username,
+    /// `#gargron_at_mastodon_social`, unique across instances
+    Acct,
+    None,
+}
+
+/// Which tags end up in Memos and how they are spelled.
+pub struct TagPolicy {
+    pub author: AuthorTag,
+    /// MEMOS_HASHTAG_ALLOW: if non-empty, only these hashtags are kept
+    allow: Vec<String>,
+    /// MEMOS_HASHTAG_DENY plus the trigger hashtags: hashtags that never become Memos tags
+    deny: Vec<String>,
+    /// Our own server, for local accounts whose `acct` has no domain
+    local_domain: String,
+}
+
+impl TagPolicy {
+    /// Trigger hashtags like `#memos` only say "save this", so they are denied too unless
+    /// MEMOS_HASHTAG_ALLOW lists them.
+    pub fn from_env(mastodon_url: &str, triggers: &[Trigger]) -> Result<Self> {
+        let author = match std::env::var("MEMOS_AUTHOR_TAG").unwrap_or_default().to_lowercase().as_str() {
+            "" | "username" => AuthorTag::Username,
+            "acct" | "user_at_domain" => AuthorTag::Acct,
+            "none" | "false" => AuthorTag::None,
+            other => bail!("Unknown MEMOS_AUTHOR_TAG '{}' (expected username, acct or none)", other),
+        };
+        let list = |key: &str| -> Vec<String> {
+            std::env::var(key).unwrap_or_default()
+                .split(',')
+                .filter_map(normalize)
+                .map(|t| t.to_lowercase())
+                .collect()
+        };
+        let local_domain = mastodon_url.split("://").last().unwrap_or_default()
+            .split('/').next().unwrap_or_default()
+            .to_string();
+        let allow = list("MEMOS_HASHTAG_ALLOW");
+        let mut deny = list("MEMOS_HASHTAG_DENY");
+        for trigger in triggers {
+            if let TriggerKind::Hashtag(tag) = &trigger.kind {
+                let tag = normalize(tag).map(|t| t.to_lowercase()).unwrap_or_default();
+                if !tag.is_empty() && !allow.contains(&tag) && !deny.contains(&tag) {
+                    deny.push(tag);
+                }
+            }
+        }
+        Ok(TagPolicy { author, allow, deny, local_domain })
+    }
+
+    /// True if a status hashtag may become a Memos tag.
+    pub fn allows(&self, tag: &str) -> bool {
+        let Some(tag) = normalize(tag).map(|t| t.to_lowercase()) else {
+            return false;
+        };
+        (self.allow.is_empty() || self.allow.contains(&tag)) && !self.deny.contains(&tag)
+    }
+
+    /// The author's tag, normalized, per MEMOS_AUTHOR_TAG.
+    pub fn author_tag(&self, account: &Account) -> Option<String> {
+        let username = account.username.as_str();
+        match self.author {
+            AuthorTag::Username => normalize(username),
+            AuthorTag::Acct => {
+                let acct = Some(account.acct.as_str()).filter(|a| !a.is_empty()).unwrap_or(username);
+                let (user, domain) = acct.split_once('@').unwrap_or((acct, &self.local_domain));
+                normalize(&format!("{}_at_{}", user, domain))
+            }
+            AuthorTag::None => None,
+        }
+    }
+
+    /// The status's own hashtags that pass the allow/deny lists, normalized.
+    pub fn status_tags(&self, status: &Status) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for name in status.tags.iter().map(|t| t.name.as_str()) {
+            if let Some(tag) = normalize(name).filter(|_| self.allows(name)) {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                    tags.push(tag);
+                }
+            }
+        }
+        tags
+    }
+}
+
+/// Turns arbitrary text into a valid Memos tag: letters, digits, `_`, `-` and `/` are kept,
+/// runs of anything else become a single `_`. Returns `None` if nothing usable is left.
+pub fn normalize(raw: &str) -> Option<String> {
+    let mut tag = String::new();
+    for c in raw.trim().trim_start_matches('#').chars() {
+        if c.is_alphanumeric() || matches!(c, '_' | '-' | '/') {
+            tag.push(c);
+        } else if !tag.ends_with('_') {
+            tag.push('_');
+        }
+    }
+    let tag = tag.trim_matches(['_', '/']).to_string();
+    (!tag.is_empty()).then_some(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mastodon_client::Tag;
+
+    fn policy(author: AuthorTag, allow: &[&str], deny: &[&str]) -> TagPolicy {
+        TagPolicy {
+            author,
+            allow: allow.iter().map(|t| t.to_string()).collect(),
+            deny: deny.iter().map(|t| t.to_string()).collect(),
+            local_domain: "example.social".to_string(),
+        }
+    }
+
+    #[test]
+    fn normalize_keeps_valid_tag_characters() {
+        assert_eq!(normalize("#rust-lang/async_io").as_deref(), Some("rust-lang/async_io"));
+        assert_eq!(normalize("Café").as_deref(), Some("Café"));
+    }
+
+    #[test]
+    fn normalize_collapses_everything_else() {
+        assert_eq!(normalize("jane.doe").as_deref(), Some("jane_doe"));
+        assert_eq!(normalize("  a . . b  ").as_deref(), Some("a_b"));
+        assert_eq!(normalize("/x!/").as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn normalize_rejects_empty_tags() {
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize("#"), None);
+        assert_eq!(normalize("!?"), None);
+    }
+
+    #[test]
+    fn allow_and_deny_lists_ignore_case_and_hash() {
+        let p = policy(AuthorTag::None, &[], &["memos"]);
+        assert!(!p.allows("#Memos"));
+        assert!(p.allows("rust"));
+        let p = policy(AuthorTag::None, &["rust"], &[]);
+        assert!(p.allows("Rust"));
+        assert!(!p.allows("go"));
+    }
+
+    #[test]
+    fn status_tags_are_filtered_and_deduplicated() {
+        let p = policy(AuthorTag::None, &[], &["memos"]);
+        let tags = ["memos", "Rust", "rust", "c++"].map(|name| Tag { name: name.into(), url: String::new() });
+        let status = Status { tags: tags.to_vec(), ..Default::default() };
+        assert_eq!(p.status_tags(&status), vec!["Rust", "c"]);
+    }
+
+    #[test]
+    fn author_tags_per_setting() {
+        let local = Account { username: "jane.doe".into(), acct: "jane.doe".into(), ..Default::default() };
+        let remote = Account { username: "jane".into(), acct: "jane@mastodon.social".into(), ..Default::default() };
+        assert_eq!(policy(AuthorTag::Username, &[], &[]).author_tag(&remote).as_deref(), Some("jane"));
+        assert_eq!(policy(AuthorTag::Acct, &[], &[]).author_tag(&local).as_deref(), Some("jane_doe_at_example_social"));
+        assert_eq!(policy(AuthorTag::Acct, &[], &[]).author_tag(&remote).as_deref(), Some("jane_at_mastodon_social"));
+        assert_eq!(policy(AuthorTag::None, &[], &[]).author_tag(&remote), None);
+    }
+
+    #[test]
+    fn trigger_hashtags_are_denied() {
+        let triggers = crate::triggers::parse("tag:#Memos, bookmark", "").unwrap();
+        let p = TagPolicy::from_env("https://example.social", &triggers).unwrap();
+        assert!(!p.allows("memos"));
+        assert!(p.allows("bookmark"));
+    }
+}
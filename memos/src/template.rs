@@ -0,0 +1,226 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::tags::TagPolicy;
+use crate::triggers::Trigger;
+
+/// Built-in memo layout, used unless MEMOS_TEMPLATE points at a file.
+const DEFAULT_TEMPLATE: &str = include_str!("../templates/memo.md");
+/// Built-in tag line, used unless MEMOS_TAGS is set.
+const DEFAULT_TAGS: &str = "#mastodon #mastodon2memos{{#if author.tag}} #{{author.tag}}{{/if}}{{#each hashtags}} #{{this}}{{/each}}";
+
+/// The user-editable parts of a memo: the body of each post, the tag line after it, and
+/// which tags are allowed.
+pub struct Layout {
+    pub body: Template,
+    pub tags: Template,
+    pub tag_policy: TagPolicy,
+}
+
+impl Layout {
+    /// Loads MEMOS_TEMPLATE (a file path), MEMOS_TAGS and the tag policy, falling back to the
+    /// built-in layout.
+    pub fn from_env(mastodon_url: &str, triggers: &[Trigger]) -> Result<Self> {
+        let body = match std::env::var("MEMOS_TEMPLATE") {
+            Ok(path) if !path.is_empty() => std::fs::read_to_string(&path)
+                .with_context(|| format!("Couldn't read MEMOS_TEMPLATE {}", path))?,
+            _ => DEFAULT_TEMPLATE.to_string(),
+        };
+        let tags = std::env::var("MEMOS_TAGS").unwrap_or_else(|_| DEFAULT_TAGS.to_string());
+        Ok(Layout {
+            body: Template::parse(&body).context("Invalid MEMOS_TEMPLATE")?,
+            tags: Template::parse(&tags).context("Invalid MEMOS_TAGS")?,
+            tag_policy: TagPolicy::from_env(mastodon_url, triggers)?,
+        })
+    }
+}
+
+/// A small Handlebars-style template for memo bodies.
+///
+/// Supports `{{path.to.field}}`, `{{#if field}}…{{else}}…{{/if}}` and
+/// `{{#each list}}…{{/each}}` (inside which `{{this}}` is the current item and
+/// item fields are looked up first). Values are inserted verbatim, since the
+/// output is Markdown rather than HTML.
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+enum Node {
+    Text(String),
+    Var(String),
+    If(String, Vec<Node>, Vec<Node>),
+    Each(String, Vec<Node>),
+}
+
+impl Template {
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut rest = src;
+        let nodes = parse_nodes(&mut rest, None)?;
+        Ok(Template { nodes })
+    }
+
+    pub fn render(&self, ctx: &Value) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &[ctx], &mut out);
+        out
+    }
+}
+
+/// Parses until the closing tag of `block` (or the end of input for the top level).
+fn parse_nodes(rest: &mut &str, block: Option<&str>) -> Result<Vec<Node>> {
+    let mut nodes = Vec::new();
+    loop {
+        let Some(start) = rest.find("{{") else {
+            if let Some(block) = block {
+                bail!("Template is missing {{{{/{}}}}}", block);
+            }
+            if !rest.is_empty() {
+                nodes.push(Node::Text(rest.to_string()));
+            }
+            *rest = "";
+            return Ok(nodes);
+        };
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_string()));
+        }
+        let Some(len) = rest[start..].find("}}") else {
+            bail!("Unclosed '{{{{' in template");
+        };
+        let tag = rest[start + 2..start + len].trim().to_string();
+        *rest = &rest[start + len + 2..];
+
+        if let Some(field) = tag.strip_prefix("#if ") {
+            let mut then = parse_nodes(rest, Some("if"))?;
+            // An {{else}} splits the block; parse_nodes leaves it as a marker
+            let otherwise = match then.iter().position(|n| matches!(n, Node::Var(v) if v == "else")) {
+                Some(i) => then.split_off(i).into_iter().skip(1).collect(),
+                None => Vec::new(),
+            };
+            nodes.push(Node::If(field.trim().to_string(), then, otherwise));
+        } else if let Some(field) = tag.strip_prefix("#each ") {
+            nodes.push(Node::Each(field.trim().to_string(), parse_nodes(rest, Some("each"))?));
+        } else if tag == "else" {
+            // Only an {{#if}} can branch; anywhere else it would silently render nothing
+            if block != Some("if") {
+                bail!("{{{{else}}}} outside of {{{{#if}}}} in template");
+            }
+            if nodes.iter().any(|n| matches!(n, Node::Var(v) if v == "else")) {
+                bail!("More than one {{{{else}}}} in an {{{{#if}}}}");
+            }
+            nodes.push(Node::Var(tag));
+        } else if let Some(closing) = tag.strip_prefix('/') {
+            if block != Some(closing.trim()) {
+                bail!("Unexpected {{{{/{}}}}} in template", closing.trim());
+            }
+            return Ok(nodes);
+        } else {
+            nodes.push(Node::Var(tag));
+        }
+    }
+}
+
+fn render_nodes(nodes: &[Node], scopes: &[&Value], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(path) => match lookup(scopes, path) {
+                Value::String(s) => out.push_str(s),
+                Value::Number(n) => out.push_str(&n.to_string()),
+                Value::Bool(b) => out.push_str(&b.to_string()),
+                _ => {}
+            },
+            Node::If(path, then, otherwise) => {
+                let branch = if truthy(lookup(scopes, path)) { then } else { otherwise };
+                render_nodes(branch, scopes, out);
+            }
+            Node::Each(path, body) => {
+                for item in lookup(scopes, path).as_array().into_iter().flatten() {
+                    let mut inner = scopes.to_vec();
+                    inner.push(item);
+                    render_nodes(body, &inner, out);
+                }
+            }
+        }
+    }
+}
+
+/// Resolves a dotted path against the innermost scope that has it.
+fn lookup<'a>(scopes: &[&'a Value], path: &str) -> &'a Value {
+    static NULL: Value = Value::Null;
+    if path == "this" {
+        return scopes.last().copied().unwrap_or(&NULL);
+    }
+    for scope in scopes.iter().rev() {
+        let found = path.split('.').try_fold(*scope, |v, key| v.get(key));
+        if let Some(value) = found {
+            return value;
+        }
+    }
+    &NULL
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(src: &str, ctx: Value) -> String {
+        Template::parse(src).unwrap().render(&ctx)
+    }
+
+    #[test]
+    fn fields_are_looked_up_by_path() {
+        let ctx = json!({ "author": { "name": "Jane", "followers": 3, "bot": false } });
+        assert_eq!(render("{{author.name}} ({{ author.followers }}, {{author.bot}}){{missing}}", ctx), "Jane (3, false)");
+    }
+
+    #[test]
+    fn if_else_follows_truthiness() {
+        let src = "{{#if spoiler}}CW {{spoiler}}{{else}}open{{/if}}";
+        assert_eq!(render(src, json!({ "spoiler": "food" })), "CW food");
+        assert_eq!(render(src, json!({ "spoiler": "" })), "open");
+        assert_eq!(render(src, json!({})), "open");
+        assert_eq!(render("{{#if n}}yes{{/if}}", json!({ "n": 0 })), "");
+    }
+
+    #[test]
+    fn each_sees_items_and_outer_fields() {
+        let ctx = json!({ "tags": ["a", "b"], "media": [{ "url": "x" }, {}], "url": "outer" });
+        assert_eq!(render("{{#each tags}} #{{this}}{{/each}}", ctx.clone()), " #a #b");
+        assert_eq!(render("{{#each media}}[{{url}}]{{/each}}", ctx), "[x][outer]");
+    }
+
+    #[test]
+    fn unbalanced_blocks_are_rejected() {
+        assert!(Template::parse("{{#if a}}never closed").is_err());
+        assert!(Template::parse("{{#each a}}{{/if}}").is_err());
+        assert!(Template::parse("{{/each}}").is_err());
+        assert!(Template::parse("{{oops").is_err());
+    }
+
+    #[test]
+    fn else_only_belongs_to_if() {
+        assert!(Template::parse("{{#each media}}{{url}}{{else}}none{{/each}}").is_err());
+        assert!(Template::parse("{{else}}").is_err());
+        assert!(Template::parse("{{#if a}}1{{else}}2{{else}}3{{/if}}").is_err());
+        let nested = "{{#each media}}{{#if url}}{{url}}{{else}}-{{/if}}{{/each}}";
+        assert_eq!(render(nested, json!({ "media": [{ "url": "x" }, { "url": "" }] })), "x-");
+    }
+
+    #[test]
+    fn default_layout_parses() {
+        assert!(Template::parse(DEFAULT_TEMPLATE).is_ok());
+        assert!(Template::parse(DEFAULT_TAGS).is_ok());
+    }
+}
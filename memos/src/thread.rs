@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+use crate::mastodon::{Mastodon, Status};
+
+/// How much of a conversation to save along with the triggered post.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadMode {
+    /// Only the post itself
+    Off,
+    /// Ancestors, the post and the author's self-replies in one memo
+    Single,
+    /// One memo per post, each referencing the previous one
+    Linked,
+}
+
+impl ThreadMode {
+    /// Reads MEMOS_THREAD: `off` (default), `single` or `linked`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("MEMOS_THREAD").unwrap_or_default().to_lowercase().as_str() {
+            "" | "off" | "false" => Ok(ThreadMode::Off),
+            "single" | "memo" => Ok(ThreadMode::Single),
+            "linked" | "relations" => Ok(ThreadMode::Linked),
+            other => bail!("Unknown MEMOS_THREAD '{}' (expected off, single or linked)", other),
+        }
+    }
+}
+
+/// Fetches the conversation around `status` and returns it in reading order: every
+/// ancestor, the status itself, then the author's own replies continuing the thread.
+///
+/// Replies in `skip` (statuses the bot posted itself) and everything below them are left out.
+pub async fn fetch(mastodon: &Mastodon, status: &Status, skip: &HashSet<String>) -> Result<Vec<Status>> {
+    let context = mastodon.context(&status.id).await?;
+
+    let mut thread = context.ancestors;
+    thread.push(status.clone());
+
+    // Descendants come depth-first, so a self-reply always follows the post it answers.
+    // Only keep replies that chain back to the status through the author's own posts.
+    let mut chain: HashSet<String> = HashSet::from([status.id.clone()]);
+    for reply in context.descendants {
+        let parent = reply.in_reply_to_id.as_deref().unwrap_or_default();
+        if reply.account.id == status.account.id && chain.contains(parent) && !skip.contains(&reply.id) {
+            chain.insert(reply.id.clone());
+            thread.push(reply);
+        }
+    }
+    Ok(thread)
+}
@@ -0,0 +1,412 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use tracing::{debug, error, warn};
+
+use crate::links;
+use crate::mastodon::{Conversation, Mastodon, NewStatus, Status};
+
+/// Upper bound on bookmark pages fetched per cycle (40 bookmarks each).
+const BOOKMARK_PAGES: usize = 25;
+/// Upper bound on own-timeline pages scanned per cycle (40 statuses each).
+const TIMELINE_PAGES: usize = 10;
+
+/// What makes a status eligible for syncing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerKind {
+    /// One of our own posts (or boosts) carrying this hashtag
+    Hashtag(String),
+    /// Any post we've bookmarked
+    Bookmark,
+    /// Any post we've favourited
+    Favourite,
+    /// A direct message to this account that links to a status
+    DirectMessage,
+}
+
+/// Where a captured status ends up in Memos.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub visibility: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub route: Route,
+    pub cleanup: CleanupAction,
+}
+
+/// What to do with the trigger on Mastodon once the memo is saved (MEMOS_CLEANUP or `!action`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CleanupAction {
+    /// Undo whatever fired the trigger: unreblog tagged boosts, remove bookmarks and
+    /// favourites, dismiss DM conversations. Tagged posts of our own are kept.
+    Auto,
+    Delete,
+    Unreblog,
+    Unbookmark,
+    /// Favourite the status to mark it as done
+    Favourite,
+    /// Reply to the status with MEMOS_REPLY_TEXT
+    Reply,
+    Nothing,
+}
+
+impl CleanupAction {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "auto" => CleanupAction::Auto,
+            "delete" => CleanupAction::Delete,
+            "unreblog" | "unboost" => CleanupAction::Unreblog,
+            "unbookmark" => CleanupAction::Unbookmark,
+            "favourite" | "favorite" => CleanupAction::Favourite,
+            "reply" => CleanupAction::Reply,
+            // Nothing is touched on Mastodon unless a cleanup is configured
+            "" | "none" | "nothing" => CleanupAction::Nothing,
+            other => bail!("Unknown cleanup '{}' (expected auto, delete, unreblog, unbookmark, favourite, reply or none)", other),
+        })
+    }
+}
+
+/// How to clear the trigger on Mastodon once the memo is saved.
+#[derive(Debug, Clone)]
+pub enum Cleanup {
+    DeleteStatus(String),
+    Unreblog(String),
+    Unbookmark(String),
+    Unfavourite(String),
+    DismissConversation(String),
+    Favourite(String),
+    Reply { id: String, text: String },
+    Nothing,
+}
+
+impl Cleanup {
+    /// Resolves the configured action for `status`, falling back to the trigger's own `auto` cleanup.
+    fn resolve(action: CleanupAction, status: &Status, auto: Cleanup, reply_text: &str) -> Cleanup {
+        let id = status.id.clone();
+        match action {
+            CleanupAction::Auto => auto,
+            CleanupAction::Delete => Cleanup::DeleteStatus(id),
+            // Unreblogging works on the boosted post, not our reblog wrapper
+            CleanupAction::Unreblog => Cleanup::Unreblog(status.reblog.as_ref().map(|r| r.id.clone()).unwrap_or(id)),
+            CleanupAction::Unbookmark => Cleanup::Unbookmark(id),
+            CleanupAction::Favourite => Cleanup::Favourite(id),
+            CleanupAction::Reply => Cleanup::Reply { id, text: reply_text.to_string() },
+            CleanupAction::Nothing => Cleanup::Nothing,
+        }
+    }
+
+    /// True for cleanups that destroy the original post, which need a verified memo first.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Cleanup::DeleteStatus(_))
+    }
+
+    /// Clears (or marks) the trigger on Mastodon. Returns the ID of the status posted, if any.
+    pub async fn run(&self, mastodon: &Mastodon) -> Result<Option<String>> {
+        match self {
+            Cleanup::DeleteStatus(id) => mastodon.delete_status(id).await.map(|_| None),
+            Cleanup::Unreblog(id) => mastodon.unreblog(id).await.map(|_| None),
+            Cleanup::Unbookmark(id) => mastodon.unbookmark(id).await.map(|_| None),
+            Cleanup::Unfavourite(id) => mastodon.unfavourite(id).await.map(|_| None),
+            Cleanup::DismissConversation(id) => mastodon.remove_conversation(id).await.map(|_| None),
+            Cleanup::Favourite(id) => mastodon.favourite(id).await.map(|_| None),
+            Cleanup::Reply { id, text } => {
+                // Direct visibility keeps the acknowledgement out of everyone's timeline
+                let reply = mastodon.post_status(&NewStatus::new(text).in_reply_to(id).visibility("direct")).await?;
+                Ok(Some(reply.id))
+            }
+            Cleanup::Nothing => Ok(None),
+        }
+    }
+}
+
+/// A status that fired a trigger.
+pub struct Capture {
+    pub status: Status,
+    pub route: Route,
+    pub cleanup: Cleanup,
+    /// Save the post a status links to rather than the status itself (hashtag and DM triggers)
+    pub follow_links: bool,
+}
+
+/// Result of one polling cycle.
+pub struct Poll {
+    /// Captures found, oldest first
+    pub captures: Vec<Capture>,
+    /// Newest status on our own timeline, to resume from next cycle
+    pub newest_own_id: Option<String>,
+    /// Last error from a failing source, for health reporting
+    pub error: Option<anyhow::Error>,
+}
+
+impl Capture {
+    pub fn status_id(&self) -> &str {
+        &self.status.id
+    }
+}
+
+/// Parses MEMOS_TRIGGERS, e.g. `tag:memos, bookmark=PROTECTED+reading, dm`.
+///
+/// Each entry is `kind[:arg][=VISIBILITY][+tag...][!cleanup]`, where kind is `tag:<name>`,
+/// `bookmark`, `favourite` or `dm`. Visibility defaults to PRIVATE, cleanup to `default_cleanup`.
+pub fn parse(spec: &str, default_cleanup: &str) -> Result<Vec<Trigger>> {
+    let default_cleanup = CleanupAction::parse(default_cleanup)?;
+    let mut triggers = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (rule, cleanup) = match entry.split_once('!') {
+            Some((rule, action)) => (rule, CleanupAction::parse(action)?),
+            None => (entry, default_cleanup),
+        };
+        let mut parts = rule.split('+').map(str::trim);
+        let head = parts.next().unwrap_or_default();
+        let tags = parts.filter(|t| !t.is_empty()).map(|t| t.trim_start_matches('#').to_string()).collect();
+
+        let (kind, visibility) = match head.split_once('=') {
+            Some((kind, vis)) => (kind.trim(), vis.trim().to_uppercase()),
+            None => (head, "PRIVATE".to_string()),
+        };
+        if !matches!(visibility.as_str(), "PRIVATE" | "PROTECTED" | "PUBLIC") {
+            bail!("Unknown visibility '{}' in trigger '{}' (expected PRIVATE, PROTECTED or PUBLIC)", visibility, entry);
+        }
+
+        let kind = match kind.to_lowercase().as_str() {
+            "bookmark" | "bookmarks" => TriggerKind::Bookmark,
+            "favourite" | "favourites" | "favorite" | "favorites" => TriggerKind::Favourite,
+            "dm" | "direct" => TriggerKind::DirectMessage,
+            other => match other.strip_prefix("tag:") {
+                Some(tag) if !tag.is_empty() => TriggerKind::Hashtag(tag.trim_start_matches('#').to_string()),
+                _ => bail!("Unknown trigger '{}' (expected tag:<name>, bookmark, favourite or dm)", entry),
+            },
+        };
+        triggers.push(Trigger { kind, route: Route { visibility, tags }, cleanup });
+    }
+    if triggers.is_empty() {
+        bail!("MEMOS_TRIGGERS doesn't contain any trigger");
+    }
+    Ok(triggers)
+}
+
+/// True if the status (or the post it boosts) carries the hashtag.
+fn has_tag(status: &Status, name: &str) -> bool {
+    status.tags.iter()
+        .chain(status.reblog.iter().flat_map(|r| &r.tags))
+        .any(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+/// Fetches our own statuses newer than `since_id`, newest first, walking back page by page.
+///
+/// Without a `since_id` (first run) only the latest page is scanned rather than the whole history.
+async fn own_statuses(mastodon: &Mastodon, my_id: &str, since_id: Option<&str>) -> Result<Vec<Status>> {
+    let mut statuses = Vec::new();
+    let mut max_id: Option<String> = None;
+
+    for _ in 0..TIMELINE_PAGES {
+        let mut query = vec![("limit", "40")];
+        if let Some(since) = since_id {
+            query.push(("since_id", since));
+        }
+        if let Some(max) = &max_id {
+            query.push(("max_id", max));
+        }
+
+        let page = mastodon.account_statuses(my_id, &query).await?;
+        let Some(oldest) = page.last() else {
+            break;
+        };
+        max_id = Some(oldest.id.clone());
+        statuses.extend(page);
+
+        if since_id.is_none() {
+            break;
+        }
+    }
+    Ok(statuses)
+}
+
+/// Polls every configured trigger source and returns the captures found.
+///
+/// A failing source is logged and skipped so the others still run. `bot_replies` are the
+/// statuses this bot posted (failure reports, acknowledgements).
+pub async fn collect(
+    mastodon: &Mastodon,
+    triggers: &[Trigger],
+    my_id: &str,
+    since_id: Option<&str>,
+    bot_replies: &HashSet<String>,
+    reply_text: &str,
+) -> Poll {
+    let mut captures = Vec::new();
+    let mut last_error = None;
+    let mut newest_own_id = None;
+    let mut seen = HashSet::new();
+
+    // Every hashtag trigger scans the same timeline, so fetch it once
+    let mut own: Option<Vec<Status>> = None;
+    if triggers.iter().any(|t| matches!(t.kind, TriggerKind::Hashtag(_))) {
+        match own_statuses(mastodon, my_id, since_id).await {
+            Ok(statuses) => {
+                newest_own_id = statuses.first().map(|s| s.id.clone());
+                own = Some(statuses);
+            }
+            Err(e) => {
+                error!(account = %my_id, error = %e, "❌ Couldn't fetch our own timeline");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    // Sources are walked in configured order, so when a post fires several triggers the
+    // first configured one wins
+    for trigger in triggers {
+        if let TriggerKind::Hashtag(tag) = &trigger.kind {
+            for status in own.iter().flatten().filter(|s| has_tag(s, tag)) {
+                if !seen.insert(status.id.clone()) {
+                    continue;
+                }
+                // Our own posts are only ever deleted when `delete` is configured explicitly
+                let auto = match &status.reblog {
+                    Some(boosted) => Cleanup::Unreblog(boosted.id.clone()),
+                    None => Cleanup::Nothing,
+                };
+                let cleanup = Cleanup::resolve(trigger.cleanup, status, auto, reply_text);
+                captures.push(Capture { status: status.clone(), route: trigger.route.clone(), cleanup, follow_links: true });
+            }
+            continue;
+        }
+
+        let found = match trigger.kind {
+            TriggerKind::Hashtag(_) => continue,
+            // Bookmarks are an inbox, so walk every page instead of just the newest ones
+            TriggerKind::Bookmark => mastodon.bookmarks(BOOKMARK_PAGES).await
+                .map(|statuses| capture_all(statuses, trigger, reply_text, Cleanup::Unbookmark)),
+            TriggerKind::Favourite => mastodon.favourites(1).await
+                .map(|statuses| capture_all(statuses, trigger, reply_text, Cleanup::Unfavourite)),
+            TriggerKind::DirectMessage => match mastodon.conversations().await {
+                Ok(conversations) => Ok(capture_requests(mastodon, conversations, trigger, my_id, bot_replies, reply_text).await),
+                Err(e) => Err(e),
+            },
+        };
+        match found {
+            Ok(found) => captures.extend(found.into_iter().filter(|c| seen.insert(c.status_id().to_string()))),
+            Err(e) => {
+                error!(trigger = ?trigger.kind, error = %e, "❌ Couldn't fetch the trigger's posts");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    // Oldest first across all sources; RFC 3339 timestamps in UTC sort as strings
+    captures.sort_by(|a, b| a.status.created_at.cmp(&b.status.created_at));
+    Poll { captures, newest_own_id, error: last_error }
+}
+
+/// Captures every bookmarked or favourited status, undone by `undo` under `auto` cleanup.
+fn capture_all(statuses: Vec<Status>, trigger: &Trigger, reply_text: &str, undo: fn(String) -> Cleanup) -> Vec<Capture> {
+    statuses.into_iter()
+        .map(|status| {
+            let cleanup = Cleanup::resolve(trigger.cleanup, &status, undo(status.id.clone()), reply_text);
+            Capture { status, route: trigger.route.clone(), cleanup, follow_links: false }
+        })
+        .collect()
+}
+
+/// Captures the DM conversations whose latest request links to a post.
+///
+/// The capture is always the request itself: when the bot has answered it (a failure report
+/// or an acknowledgement), the status it answered is fetched, so a failed request keeps
+/// being retried under its own ID until it's saved.
+async fn capture_requests(
+    mastodon: &Mastodon,
+    conversations: Vec<Conversation>,
+    trigger: &Trigger,
+    my_id: &str,
+    bot_replies: &HashSet<String>,
+    reply_text: &str,
+) -> Vec<Capture> {
+    let mut captures = Vec::new();
+    for conversation in conversations {
+        let Some(last) = conversation.last_status else {
+            continue;
+        };
+        let status = if last.account.id != my_id {
+            last
+        } else if let Some(request) = last.in_reply_to_id.as_deref().filter(|_| bot_replies.contains(&last.id)) {
+            match mastodon.status(request).await {
+                Ok(status) if status.account.id != my_id => status,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(conversation = %conversation.id, status_id = %request, error = %e, "⚠️ Couldn't fetch the DM the bot answered");
+                    continue;
+                }
+            }
+        } else {
+            // Anything else we wrote ourselves is ordinary conversation, not a request
+            debug!(conversation = %conversation.id, "Last DM is our own, ignoring");
+            continue;
+        };
+        // Only DMs that point at a post are captures; anything else is ordinary conversation
+        if links::status_urls(&status.content).is_empty() {
+            debug!(conversation = %conversation.id, "DM without a status link, ignoring");
+            continue;
+        }
+        let cleanup = Cleanup::resolve(trigger.cleanup, &status, Cleanup::DismissConversation(conversation.id), reply_text);
+        captures.push(Capture { status, route: trigger.route.clone(), cleanup, follow_links: true });
+    }
+    captures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(spec: &str) -> Trigger {
+        let mut triggers = parse(spec, "").unwrap();
+        assert_eq!(triggers.len(), 1, "{}", spec);
+        triggers.remove(0)
+    }
+
+    #[test]
+    fn every_kind_parses_with_its_aliases() {
+        assert_eq!(single("tag:memos").kind, TriggerKind::Hashtag("memos".into()));
+        assert_eq!(single("tag:#Read").kind, TriggerKind::Hashtag("read".into()));
+        for spec in ["bookmark", "Bookmarks"] {
+            assert_eq!(single(spec).kind, TriggerKind::Bookmark);
+        }
+        for spec in ["favourite", "favourites", "favorite", "FAVORITES"] {
+            assert_eq!(single(spec).kind, TriggerKind::Favourite);
+        }
+        for spec in ["dm", "direct"] {
+            assert_eq!(single(spec).kind, TriggerKind::DirectMessage);
+        }
+    }
+
+    #[test]
+    fn visibility_tags_and_cleanup_are_read_per_entry() {
+        let triggers = parse("tag:memos, bookmark=protected+reading+#later!unbookmark, dm!reply", "favourite").unwrap();
+        assert_eq!(triggers.len(), 3);
+        assert_eq!(triggers[0].route.visibility, "PRIVATE");
+        assert!(triggers[0].route.tags.is_empty());
+        assert_eq!(triggers[0].cleanup, CleanupAction::Favourite);
+        assert_eq!(triggers[1].route.visibility, "PROTECTED");
+        assert_eq!(triggers[1].route.tags, vec!["reading", "later"]);
+        assert_eq!(triggers[1].cleanup, CleanupAction::Unbookmark);
+        assert_eq!(triggers[2].cleanup, CleanupAction::Reply);
+    }
+
+    #[test]
+    fn cleanup_defaults_to_nothing() {
+        assert_eq!(single("bookmark").cleanup, CleanupAction::Nothing);
+        assert_eq!(parse("bookmark", "none").unwrap()[0].cleanup, CleanupAction::Nothing);
+        assert_eq!(parse("bookmark", "auto").unwrap()[0].cleanup, CleanupAction::Auto);
+    }
+
+    #[test]
+    fn malformed_specs_are_rejected() {
+        for spec in ["", " , ", "tag:", "tag", "boost", "bookmark=SECRET", "bookmark!burn", "=PUBLIC"] {
+            assert!(parse(spec, "").is_err(), "{}", spec);
+        }
+        assert!(parse("bookmark", "explode").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use reqwest::Client;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::mastodon::Status;
+
+/// A local folder of Markdown notes (e.g. an Obsidian or Logseq vault) used instead of Memos.
+///
+/// Every saved post is one `.md` file with YAML front matter; media is downloaded next to it
+/// and embedded at the end of the note. Notes and files are identified by their file name.
+pub struct Vault {
+    http: Client,
+    dir: PathBuf,
+}
+
+impl Vault {
+    pub fn new(http: Client, dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir).with_context(|| format!("Couldn't create the vault folder {}", dir.display()))?;
+        Ok(Vault { http, dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a note for `source` (the status it was saved from) and returns its file name.
+    pub fn create_note(&self, content: &str, resources: &[String], source: &Status) -> Result<String> {
+        let created_at = source.created_at.as_str();
+        let url = source.url.as_deref().unwrap_or_default();
+        let slug = Some(source.id.as_str()).filter(|id| !id.is_empty())
+            .or_else(|| url.trim_end_matches('/').rsplit('/').next().filter(|s| !s.is_empty()))
+            .unwrap_or("post");
+        let date = created_at.get(..10).unwrap_or("undated");
+        let name = self.free_name(&format!("{}-{}", date, sanitize(slug)), "md");
+
+        let mut note = String::from("---\n");
+        note.push_str(&format!("author: {}\n", yaml_string(&source.account.acct)));
+        note.push_str(&format!("author_name: {}\n", yaml_string(&source.account.display_name)));
+        note.push_str(&format!("url: {}\n", yaml_string(url)));
+        note.push_str(&format!("date: {}\n", yaml_string(created_at)));
+        let tags = note_tags(content);
+        if tags.is_empty() {
+            note.push_str("tags: []\n");
+        } else {
+            note.push_str("tags:\n");
+            for tag in tags {
+                note.push_str(&format!("  - {}\n", yaml_string(&tag)));
+            }
+        }
+        note.push_str("---\n\n");
+        note.push_str(content.trim());
+        for file in resources {
+            note.push_str(&format!("\n\n![]({})", file));
+        }
+        note.push('\n');
+
+        fs::write(self.dir.join(&name), note).with_context(|| format!("Couldn't write {}", name))?;
+        Ok(name)
+    }
+
+    pub fn note_exists(&self, name: &str) -> bool {
+        self.dir.join(name).is_file()
+    }
+
+    /// Appends `text` to the end of an existing note.
+    pub fn append(&self, name: &str, text: &str) -> Result<()> {
+        let path = self.dir.join(name);
+        if !path.is_file() {
+            bail!("Note {} doesn't exist", path.display());
+        }
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        file.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Makes `name` reference the `related` note with a wiki link.
+    pub fn link(&self, name: &str, related: &str) -> Result<()> {
+        self.append(name, &format!("\n↩️ [[{}]]\n", related.trim_end_matches(".md")))
+    }
+
+    /// Stores a file in the vault and returns its file name.
+    pub fn store_file(&self, filename: &str, bytes: &[u8]) -> Result<String> {
+        let (stem, ext) = filename.rsplit_once('.').unwrap_or((filename, "bin"));
+        let name = self.free_name(&sanitize(stem), &sanitize(ext));
+        fs::write(self.dir.join(&name), bytes).with_context(|| format!("Couldn't write {}", name))?;
+        Ok(name)
+    }
+
+    pub fn delete_file(&self, name: &str) -> Result<()> {
+        fs::remove_file(self.dir.join(name)).with_context(|| format!("Couldn't delete {}", name))
+    }
+
+    /// Downloads a remote file (e.g. Mastodon media) into the vault.
+    pub async fn copy_remote_file(&self, url: &str) -> Result<String> {
+        let resp = self.http.get(url).send().await?.error_for_status()?;
+        let filename = url.split('?').next().unwrap_or(url).rsplit('/').next().unwrap_or("file").to_string();
+        let bytes = resp.bytes().await?;
+        self.store_file(&filename, &bytes)
+    }
+
+    /// `stem.ext`, or `stem-2.ext`, `stem-3.ext`… if that's taken.
+    fn free_name(&self, stem: &str, ext: &str) -> String {
+        let mut name = format!("{}.{}", stem, ext);
+        let mut n = 2;
+        while self.dir.join(&name).exists() {
+            name = format!("{}-{}.{}", stem, n, ext);
+            n += 1;
+        }
+        name
+    }
+}
+
+/// Memos-style `#tags` in a note, for the front matter.
+fn note_tags(content: &str) -> Vec<String> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"(?:^|\s)#([\w/-]+)").unwrap());
+    let mut tags: Vec<String> = Vec::new();
+    for cap in tag.captures_iter(content) {
+        if !tags.contains(&cap[1].to_string()) {
+            tags.push(cap[1].to_string());
+        }
+    }
+    tags
+}
+
+/// A double-quoted YAML scalar (JSON string syntax is valid YAML).
+fn yaml_string(s: &str) -> String {
+    Value::String(s.to_string()).to_string()
+}
+
+/// Keeps file names portable across file systems.
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
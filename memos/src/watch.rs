@@ -0,0 +1,100 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+use bot_support::health::Metrics;
+
+use crate::mastodon::{self, Mastodon, Status};
+use crate::sink::Sink;
+use crate::state::{SyncState, Watched};
+use crate::template::Layout;
+
+/// Re-checks saved posts from others and carries upstream edits and deletions into their memos.
+pub struct Watcher {
+    /// Minimum time between two sweeps over the watch list
+    pub interval: Duration,
+    /// Stop watching a memo this long after it was saved
+    pub keep_for: Duration,
+}
+
+impl Watcher {
+    /// Reads MEMOS_WATCH (off by default), MEMOS_WATCH_MINUTES (60) and MEMOS_WATCH_DAYS (30).
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("MEMOS_WATCH").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let minutes = std::env::var("MEMOS_WATCH_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(60);
+        let days: u64 = std::env::var("MEMOS_WATCH_DAYS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+        Some(Watcher {
+            interval: Duration::from_secs(minutes * 60),
+            keep_for: Duration::from_secs(days * 24 * 60 * 60),
+        })
+    }
+
+    /// Starts watching `status`, saved as `memo`.
+    pub fn track(&self, state: &mut SyncState, status: &Status, memo: &str) {
+        if status.id.is_empty() {
+            return;
+        }
+        state.watched.insert(status.id.clone(), Watched {
+            memo: memo.to_string(),
+            edited_at: status.edited_at.clone(),
+            saved_at: now(),
+        });
+    }
+
+    /// Re-fetches every watched status: edits are appended to the memo, deleted statuses get
+    /// `#deleted-upstream` and are dropped from the list, as are entries past MEMOS_WATCH_DAYS.
+    pub async fn sweep(&self, mastodon: &Mastodon, layout: &Layout, sink: &Sink, state: &mut SyncState, metrics: &Metrics) {
+        let cutoff = now().saturating_sub(self.keep_for.as_secs());
+        state.watched.retain(|_, w| w.saved_at >= cutoff);
+
+        let ids: Vec<String> = state.watched.keys().cloned().collect();
+        for id in ids {
+            let watched = state.watched[&id].clone();
+            match mastodon.status(&id).await {
+                Ok(status) => {
+                    let edited_at = status.edited_at.clone();
+                    if edited_at.is_none() || edited_at == watched.edited_at {
+                        continue;
+                    }
+                    let (content, _) = crate::render_status(&status, layout, sink, false, metrics).await;
+                    let section = format!("\n\n---\n\n✏️ **Edited upstream** ({}):\n\n{}", edited_at.as_deref().unwrap_or_default(), content);
+                    match sink.append_to_memo(&watched.memo, &section).await {
+                        Ok(()) => {
+                            info!(status_id = %id, memo = %watched.memo, "✏️ Source was edited, memo updated");
+                            if let Some(w) = state.watched.get_mut(&id) {
+                                w.edited_at = edited_at;
+                            }
+                        }
+                        Err(e) => {
+                            error!(status_id = %id, memo = %watched.memo, error = %e, "❌ Couldn't update the memo");
+                            metrics.failure(e);
+                        }
+                    }
+                }
+                Err(e) if mastodon::is_gone(&e) => {
+                    match sink.append_to_memo(&watched.memo, "\n\n#deleted-upstream").await {
+                        Ok(()) => {
+                            info!(status_id = %id, memo = %watched.memo, "🗑️ Source was deleted, memo tagged");
+                            state.watched.remove(&id);
+                        }
+                        Err(e) => {
+                            error!(status_id = %id, memo = %watched.memo, error = %e, "❌ Couldn't tag the memo");
+                            metrics.failure(e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!(status_id = %id, error = %e, "⚠️ Couldn't re-check the source, trying again next sweep");
+                    if mastodon::is_rate_limited(&e) {
+                        metrics.rate_limited();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
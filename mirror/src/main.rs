@@ -1,37 +1,71 @@
+mod mapping;
+mod profile;
+mod schedule;
+
 use dotenv::dotenv;
 use std::env;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use serde::{Deserialize, Serialize};
-use reqwest::{Client, multipart};
+use reqwest::StatusCode;
+use mastodon_client::{Client, NewStatus, Posted, Status};
 use regex::Regex;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use bot_support::health::{self, Metrics};
+use profile::ProfileSync;
+use schedule::{QueuedPost, Schedule};
+use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Deserialize, Clone)]
-struct Status {
-    id: String,
-    content: String,
-    visibility: String,
-    in_reply_to_id: Option<String>,
-    media_attachments: Vec<Media>,
-    reblog: Option<serde_json::Value>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-struct Media {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Media {
     url: String,
     description: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-struct PostStatus<'a> {
-    status: String,
-    visibility: &'a str,
-    media_ids: Vec<String>,
+/// What to do with boosts found on the source account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoostPolicy {
+    /// Ignore boosts entirely
+    Skip,
+    /// Reblog the same post from the target account
+    Reblog,
+    /// Post a new status linking to the boosted post, crediting its author
+    Link,
+}
+
+impl BoostPolicy {
+    /// Reads BOOST_POLICY (skip | reblog | link), defaulting to skip.
+    fn from_env() -> Result<Self> {
+        match env::var("BOOST_POLICY").unwrap_or_else(|_| "skip".into()).to_lowercase().as_str() {
+            "skip" => Ok(BoostPolicy::Skip),
+            "reblog" => Ok(BoostPolicy::Reblog),
+            "link" | "quote" => Ok(BoostPolicy::Link),
+            other => anyhow::bail!("Unknown BOOST_POLICY '{}' (expected skip, reblog or link)", other),
+        }
+    }
+}
+
+/// Result of handing a queued post over to the target instance.
+enum PostOutcome {
+    Posted(String),
+    /// Handed over with `scheduled_at`; carries the scheduled status ID
+    Scheduled(String),
+    RateLimited,
+    /// Uploading took too long to still use `scheduled_at`; the post waits in the local queue
+    Deferred,
+    /// The target refused `scheduled_at` for this post
+    SchedulingRefused,
+    /// The target doesn't implement scheduled statuses at all
+    SchedulingUnsupported,
+    /// The target turned the post down; it's retried with a backoff a few times
+    Failed(String),
+    /// The target may have created the post but the answer was lost, so it's never retried
+    Unconfirmed(String),
 }
 
 /// Cleans up HTML tags from Mastodon posts and decodes HTML entities
-fn clean_html(html: &str) -> String {
+pub fn clean_html(html: &str) -> String {
     let mut text = html.replace("<br />", "\n").replace("<br>", "\n").replace("</p><p>", "\n\n");
     let re = Regex::new(r"<[^>]*>").unwrap();
     text = re.replace_all(&text, "").to_string();
@@ -44,115 +78,371 @@ fn clean_html(html: &str) -> String {
 }
 
 /// Downloads media from the source URL and uploads it to the target instance
-async fn upload_media(client: &Client, url: &str, desc: Option<String>, tgt_url: &str, tgt_token: &str) -> Result<String> {
+async fn upload_media(tgt: &Client, url: &str, desc: Option<&str>) -> Result<String> {
     // Download file
-    let resp = client.get(url).send().await?.bytes().await?;
-    let file_name = url.split('/').last().unwrap_or("file.jpg").to_string();
+    let bytes = tgt.download(url).await?;
+    let file_name = url.split('/').next_back().unwrap_or("file.jpg").to_string();
 
-    let part = multipart::Part::bytes(resp.to_vec())
-        .file_name(file_name)
-        .mime_str("application/octet-stream")?;
+    // Upload to target
+    let media = tgt.upload_media(bytes, &file_name, "application/octet-stream", desc).await
+        .map_err(|e| e.context(format!("Couldn't upload {} to {}", url, tgt.base_url())))?;
+    Ok(media.id)
+}
 
-    let mut form = multipart::Form::new().part("file", part);
-    if let Some(d) = desc { form = form.text("description", d); }
+/// Turns a failed target request into an outcome. Network hiccups count as rate limits so the
+/// post stays queued, error responses are retried a few times, and an unreadable answer to a
+/// request the server accepted is final so the post can't be created twice.
+fn failed_outcome(e: anyhow::Error, what: &str) -> PostOutcome {
+    match mastodon_client::status_of(&e) {
+        Some(StatusCode::TOO_MANY_REQUESTS) => PostOutcome::RateLimited,
+        Some(_) => PostOutcome::Failed(e.to_string()),
+        None if mastodon_client::is_network(&e) => {
+            warn!(error = %e, "🌩️ {} request failed, keeping it queued", what);
+            PostOutcome::RateLimited
+        }
+        None => PostOutcome::Unconfirmed(format!("{} response couldn't be read, not retrying in case it went through: {}", what, e)),
+    }
+}
 
-    // Upload to target
-    let res = client.post(format!("{}/api/v2/media", tgt_url))
-        .header("Authorization", format!("Bearer {}", tgt_token))
-        .multipart(form).send().await?;
+/// Builds a status that links to a boosted post, crediting its original author
+fn boost_link_text(boosted: &Status) -> String {
+    let url = boosted.url.as_deref().unwrap_or(&boosted.uri);
+    let name = if boosted.account.display_name.is_empty() { &boosted.account.acct } else { &boosted.account.display_name };
+    let mut excerpt = clean_html(&boosted.content);
+    // Leave room for the attribution and link within the usual 500-character limit
+    if excerpt.chars().count() > 300 {
+        excerpt = format!("{}…", excerpt.chars().take(300).collect::<String>().trim_end());
+    }
+    // Link the profile instead of @-mentioning, so the author doesn't get pinged by a bot
+    format!("🔁 {} ({}):\n\n“{}”\n\n{}", name, boosted.account.url, excerpt, url)
+}
+
+/// Finds a boosted post on the target instance and reblogs it from the target account
+async fn reblog(tgt: &Client, uri: &str) -> PostOutcome {
+    let found = match tgt.resolve_status(uri).await {
+        Ok(found) => found,
+        Err(e) => return failed_outcome(e, "Search"),
+    };
+    let Some(status) = found else {
+        return PostOutcome::Failed(format!("couldn't resolve {} on the target", uri));
+    };
+
+    match tgt.reblog(&status.id, Some("private")).await {
+        Ok(boost) => PostOutcome::Posted(boost.id),
+        Err(e) => failed_outcome(e, "Reblog"),
+    }
+}
 
-    if res.status().is_success() {
-        let json: serde_json::Value = res.json().await?;
-        Ok(json["id"].as_str().unwrap().to_string())
+/// Uploads the queued post's media and publishes it, as a Mastodon scheduled status if
+/// `schedule` is set and the post is still far enough ahead once its media is uploaded
+async fn publish(tgt: &Client, post: &mut QueuedPost, schedule: bool, metrics: &Metrics) -> PostOutcome {
+    if let Some(uri) = &post.reblog_of {
+        return reblog(tgt, uri).await;
+    }
+
+    // Process attachments (once: a deferred or retried post keeps what was already uploaded)
+    if post.uploaded.is_none() {
+        let mut media_ids = Vec::new();
+        for m in &post.media {
+            match upload_media(tgt, &m.url, m.description.as_deref()).await {
+                Ok(mid) => {
+                    metrics.media_uploaded();
+                    media_ids.push(mid);
+                }
+                Err(e) => {
+                    metrics.failure(&e);
+                    warn!(source_id = %post.source_id, media_url = %m.url, error = %e, "⚠️ Couldn't upload an attachment, posting without it");
+                }
+            }
+        }
+        post.uploaded = Some(media_ids);
+    }
+
+    // Uploads can take minutes, so check the scheduling lead again now that they are done
+    let now = Utc::now();
+    let scheduled_at = if schedule && schedule::can_schedule(post.due, now) {
+        Some(post.due)
+    } else if post.due <= now {
+        None
     } else {
-        // Error handling with attitude
-        anyhow::bail!("🚫 Ugh, media upload failed! My vibe is ruined. Status: {}", res.status())
+        return PostOutcome::Deferred;
+    };
+
+    // Post to target instance
+    let mut params = NewStatus::new(post.text.clone())
+        .visibility("private")
+        .spoiler(post.spoiler_text.clone())
+        .media(post.uploaded.clone().unwrap_or_default());
+    if let Some(at) = scheduled_at {
+        params = params.scheduled_at(at.to_rfc3339());
+    }
+
+    match tgt.post_scheduled_status(&params).await {
+        // Servers without scheduling support just publish right away
+        Ok(Posted::Published(status)) => PostOutcome::Posted(status.id),
+        Ok(Posted::Scheduled(scheduled)) => PostOutcome::Scheduled(scheduled.id),
+        Err(e) if scheduled_at.is_some() && mastodon_client::status_of(&e) == Some(StatusCode::NOT_IMPLEMENTED) => {
+            PostOutcome::SchedulingUnsupported
+        }
+        Err(e) if scheduled_at.is_some() && rejects_scheduled_at(&e) => PostOutcome::SchedulingRefused,
+        Err(e) => failed_outcome(e, "Post"),
     }
 }
 
+/// True for a 422 that blames `scheduled_at` (e.g. too close to now), rather than the post itself
+fn rejects_scheduled_at(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<mastodon_client::ApiError>().is_some_and(|e| {
+        e.status == StatusCode::UNPROCESSABLE_ENTITY && e.body.to_lowercase().contains("schedul")
+    })
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables
     dotenv().ok();
+    bot_support::logging::init();
     let src_url = env::var("SOURCE_URL")?;
     let src_token = env::var("SOURCE_TOKEN")?;
     let tgt_url = env::var("TARGET_URL")?;
     let tgt_token = env::var("TARGET_TOKEN")?;
 
-    let client = Client::builder().timeout(Duration::from_secs(60)).build()?;
+    // Posting rules (delay, quiet hours)
+    let mut schedule = Schedule::from_env()?;
+    let boost_policy = BoostPolicy::from_env()?;
+
+    // Optional profile + pinned posts sync
+    let sync_profile = env::var("SYNC_PROFILE").map(|v| v == "true").unwrap_or(false);
+    let profile_interval = Duration::from_secs(60 * env::var("PROFILE_SYNC_MINUTES").ok()
+        .and_then(|v| v.parse::<u64>().ok()).unwrap_or(60));
+
+    let http = reqwest::Client::builder().timeout(Duration::from_secs(60)).build()?;
+    let src = Client::with_http(http.clone(), &src_url, &src_token);
+    let tgt = Client::with_http(http, &tgt_url, &tgt_token);
+
+    // Optional /healthz + /metrics endpoint (unhealthy after ~5 missed polls)
+    let metrics = Metrics::new("mirror", Duration::from_secs(600));
+    if let Ok(addr) = env::var("HEALTH_ADDR") {
+        health::serve(addr.clone(), metrics.clone()).await?;
+        info!(%addr, "🩺 Health endpoint listening");
+    }
 
     // Verify credentials and fetch current user ID
-    let me: serde_json::Value = client.get(format!("{}/api/v1/accounts/verify_credentials", src_url))
-        .header("Authorization", format!("Bearer {}", src_token)).send().await?.json().await?;
-    let my_id = me["id"].as_str().unwrap().to_string();
+    let me = src.verify_credentials().await?;
+    let my_id = me.id;
 
-    println!("✨ Makeup ready! Mirror Queen activated! Stalking account: {} 💅", me["username"]);
+    info!(account = %me.username, source = %src_url, target = %tgt_url, "🚀 Mirror started");
 
     // Fetch the most recent post ID to establish a baseline and avoid duplicating old history
-    let mut last_id = client.get(format!("{}/api/v1/accounts/{}/statuses?limit=1", src_url, my_id))
-        .header("Authorization", format!("Bearer {}", src_token)).send().await?
-        .json::<Vec<Status>>().await?.first().map(|s| s.id.clone()).unwrap_or_default();
+    let mut last_id = src.account_statuses(&my_id, &[("limit", "1")]).await?
+        .first().map(|s| s.id.clone()).unwrap_or_default();
+
+    info!(last_id = %last_id, "🔎 Baseline set, waiting for new posts");
+
+    // Source→target IDs of everything mirrored so far (used to align pins)
+    let mut mapping = mapping::load_mapping().inspect_err(|e| error!(error = ?e, "❌ Refusing to start with an unreadable mapping"))?;
+    // Scheduled posts whose target status ID is filled in once they're published
+    let mut scheduled = mapping::load_scheduled().inspect_err(|e| error!(error = ?e, "❌ Refusing to start with unreadable scheduled posts"))?;
+    let mut profile_sync = ProfileSync::new(src.clone(), tgt.clone())?;
+    let mut next_profile_sync = Instant::now();
 
-    println!("🔎 Found the latest tea spill (ID: {}). Waiting for fresh drama... ☕", last_id);
+    // Posts left over from the previous run
+    let mut queue = schedule::load_queue().inspect_err(|e| error!(error = ?e, "❌ Refusing to start with an unreadable queue"))?;
+    let mut last_due: Option<DateTime<Utc>> = queue.iter().map(|q| q.due).max();
+
+    if !queue.is_empty() {
+        info!(queued = queue.len(), "📦 Resuming posts queued by the previous run");
+    }
 
     // Main polling loop
     loop {
-        let url = format!("{}/api/v1/accounts/{}/statuses?since_id={}", src_url, my_id, last_id);
-        let resp = client.get(url)
-            .header("Authorization", format!("Bearer {}", src_token))
-            .send().await;
+        let polled: Result<Vec<Status>> = src.account_statuses(&my_id, &[("since_id", &last_id)]).await;
+
+        match polled {
+            Ok(mut statuses) => {
+                metrics.poll_ok();
 
-        if let Ok(r) = resp {
-            if let Ok(mut statuses) = r.json::<Vec<Status>>().await {
                 // Process posts from oldest to newest
                 statuses.reverse();
 
                 for s in statuses {
-                    // Skip reblogs and replies to keep the feed clean
-                    if s.reblog.is_some() || s.in_reply_to_id.is_some() {
+                    // Skip replies to keep the feed clean
+                    if s.in_reply_to_id.is_some() {
                         last_id = s.id.clone();
                         continue;
                     }
 
-                    // Clean HTML content
-                    let text = clean_html(&s.content);
+                    let (text, spoiler_text, media, reblog_of) = match &s.reblog {
+                        None => {
+                            // Clean HTML content
+                            let text = clean_html(&s.content);
 
-                    // Skip direct mentions starting with @
-                    if text.starts_with('@') {
-                        last_id = s.id.clone();
-                        continue;
-                    }
+                            // Skip direct mentions starting with @
+                            if text.starts_with('@') {
+                                last_id = s.id.clone();
+                                continue;
+                            }
+                            let media = s.media_attachments.iter()
+                                .filter_map(|m| Some(Media { url: m.url.clone().or(m.remote_url.clone())?, description: m.description.clone() }))
+                                .collect();
+                            (text, s.spoiler_text.clone(), media, None)
+                        }
+                        Some(boosted) => match boost_policy {
+                            BoostPolicy::Skip => {
+                                last_id = s.id.clone();
+                                continue;
+                            }
+                            BoostPolicy::Reblog => (clean_html(&boosted.content), String::new(), Vec::new(), Some(boosted.uri.clone())),
+                            // The excerpt stays behind the boosted post's content warning
+                            BoostPolicy::Link => (boost_link_text(boosted), boosted.spoiler_text.clone(), Vec::new(), None),
+                        },
+                    };
 
-                    println!("💌 Ooh, fresh content incoming! (ID: {}): '{}...' Stealing it! 💖", s.id, text.chars().take(30).collect::<String>());
+                    info!(id = %s.id, preview = %text.chars().take(30).collect::<String>(), "📥 New post on the source, queued");
 
-                    // Process attachments
-                    let mut media_ids = Vec::new();
-                    for m in s.media_attachments {
-                        match upload_media(&client, &m.url, m.description, &tgt_url, &tgt_token).await {
-                            Ok(mid) => media_ids.push(mid),
-                            Err(_) => println!("⚠️ Oopsie, couldn't upload a pic. Whatever, posting without it. 🙄"),
+                    // Work out when this post is allowed to go out
+                    let mut earliest = Utc::now() + schedule.delay;
+                    if !schedule.is_immediate() {
+                        // Spread queued posts out so a whole night's worth doesn't drop at once
+                        if let Some(prev) = last_due {
+                            earliest = earliest.max(prev + schedule.spacing);
                         }
                     }
+                    let due = schedule.next_slot(earliest);
+                    last_due = Some(due);
+
+                    queue.push(QueuedPost { source_id: s.id.clone(), text, spoiler_text, media, due, reblog_of, uploaded: None, hold_locally: false, attempts: 0, retry_at: None });
+                    last_id = s.id.clone();
+                }
 
-                    // Post to target instance
-                    let params = PostStatus { status: text, visibility: "private", media_ids };
-                    let post_res = client.post(format!("{}/api/v1/statuses", tgt_url))
-                        .header("Authorization", format!("Bearer {}", tgt_token))
-                        .json(&params).send().await;
-
-                    if let Ok(pr) = post_res {
-                        if pr.status().is_success() {
-                            println!("🎉 Posted! I'm literally the best bot ever. ✨");
-                            last_id = s.id.clone();
-                        } else if pr.status() == 429 {
-                            // Handle rate limiting
-                            println!("🛑 Ugh, rate limit! Too much attention. Taking a 5-min beauty nap. Don't disturb! 😴");
-                            sleep(Duration::from_secs(300)).await;
+                if let Err(e) = schedule::save_queue(&queue) {
+                    error!(file = "queue.json", error = %e, "❌ Couldn't save the queue, keeping it in memory");
+                }
+            }
+            Err(e) => {
+                error!(url = %src_url, error = %e, "❌ Couldn't poll the source account, retrying next cycle");
+                metrics.poll_failed(e);
+            }
+        }
+
+        // Hand over everything that is due (or far enough ahead to use scheduled_at)
+        let mut remaining = Vec::new();
+        for mut post in std::mem::take(&mut queue) {
+            let now = Utc::now();
+            // Reblogs can't be scheduled server-side, so they always wait in the local queue
+            let use_scheduled_at = schedule.use_scheduled_at
+                && post.reblog_of.is_none()
+                && !post.hold_locally
+                && schedule::can_schedule(post.due, now);
+            if (!use_scheduled_at && post.due > now) || post.retry_at.is_some_and(|at| at > now) {
+                remaining.push(post);
+                continue;
+            }
+
+            match publish(&tgt, &mut post, use_scheduled_at, &metrics).await {
+                PostOutcome::Posted(target_id) => {
+                    metrics.post_ok();
+                    info!(source_id = %post.source_id, target_id = %target_id, "✅ Posted to the target");
+                    mapping.insert(post.source_id.clone(), target_id);
+                    if let Err(e) = mapping::save_mapping(&mapping) {
+                        error!(file = "mapping.json", error = %e, "❌ Couldn't save the source→target mapping");
+                    }
+                    // Short delay between posts to be polite
+                    sleep(schedule.spacing.to_std().unwrap_or_default()).await;
+                }
+                PostOutcome::Scheduled(scheduled_id) => {
+                    metrics.post_ok();
+                    info!(source_id = %post.source_id, due = %post.due, scheduled_id = %scheduled_id, "📅 Scheduled on the target");
+                    scheduled.insert(post.source_id.clone(), mapping::ScheduledPost { scheduled_id, due: post.due, text: post.text.clone() });
+                    if let Err(e) = mapping::save_scheduled(&scheduled) {
+                        error!(file = "scheduled.json", error = %e, "❌ Couldn't save the scheduled posts");
+                    }
+                }
+                PostOutcome::RateLimited => {
+                    // Handle rate limiting
+                    metrics.rate_limited();
+                    warn!(source_id = %post.source_id, url = %tgt_url, status = 429, "⏳ Rate limited by the target, pausing for 5 minutes");
+                    remaining.push(post);
+                    sleep(Duration::from_secs(300)).await;
+                }
+                PostOutcome::Deferred => {
+                    debug!(source_id = %post.source_id, due = %post.due, "Too close to its time for scheduled_at, holding it locally");
+                    remaining.push(post);
+                }
+                PostOutcome::SchedulingRefused => {
+                    warn!(source_id = %post.source_id, due = %post.due, "📅 Target refused scheduled_at for this post, holding it in the local queue");
+                    post.hold_locally = true;
+                    remaining.push(post);
+                }
+                PostOutcome::SchedulingUnsupported => {
+                    warn!(url = %tgt_url, status = 501, "⚠️ Target doesn't support scheduled posts, using the local queue from now on");
+                    schedule.use_scheduled_at = false;
+                    remaining.push(post);
+                }
+                PostOutcome::Failed(e) => {
+                    metrics.failure(&e);
+                    post.attempts += 1;
+                    if post.attempts >= schedule::MAX_POST_ATTEMPTS {
+                        error!(source_id = %post.source_id, attempts = post.attempts, error = %e, "❌ Couldn't post to the target, giving up on it");
+                    } else {
+                        let retry_at = Utc::now() + schedule::retry_delay(post.attempts);
+                        warn!(source_id = %post.source_id, attempts = post.attempts, %retry_at, error = %e, "⚠️ Couldn't post to the target, retrying later");
+                        post.retry_at = Some(retry_at);
+                        remaining.push(post);
+                    }
+                }
+                PostOutcome::Unconfirmed(e) => {
+                    metrics.failure(&e);
+                    error!(source_id = %post.source_id, error = %e, "❌ Couldn't confirm the post on the target, dropping it");
+                }
+            }
+        }
+        queue = remaining;
+        metrics.set_queue_depth(queue.len());
+        if let Err(e) = schedule::save_queue(&queue) {
+            error!(file = "queue.json", error = %e, "❌ Couldn't save the queue, keeping it in memory");
+        }
+
+        // Learn the target IDs of scheduled posts that have gone out, so they can be pinned too
+        if scheduled.values().any(|p| p.due <= Utc::now()) {
+            let before = scheduled.len();
+            match mapping::resolve_scheduled(&tgt, &mut scheduled, &mut mapping).await {
+                Ok(resolved) => {
+                    if resolved > 0 {
+                        info!(resolved, "🔗 Matched published scheduled posts to their target statuses");
+                        if let Err(e) = mapping::save_mapping(&mapping) {
+                            error!(file = "mapping.json", error = %e, "❌ Couldn't save the source→target mapping");
+                        }
+                    }
+                    if scheduled.len() != before {
+                        if let Err(e) = mapping::save_scheduled(&scheduled) {
+                            error!(file = "scheduled.json", error = %e, "❌ Couldn't save the scheduled posts");
                         }
                     }
+                }
+                Err(e) => {
+                    metrics.failure(&e);
+                    warn!(url = %tgt_url, error = %e, "⚠️ Couldn't check on scheduled posts, retrying next cycle");
+                }
+            }
+        }
 
-                    // Short delay between posts to be polite
-                    sleep(Duration::from_secs(10)).await;
+        // Keep the target's profile and pins looking like the source
+        if sync_profile && Instant::now() >= next_profile_sync {
+            next_profile_sync = Instant::now() + profile_interval;
+            match profile_sync.sync_profile().await {
+                Ok(true) => info!(url = %tgt_url, "🪞 Profile copied to the target"),
+                Ok(false) => debug!("Source profile unchanged"),
+                Err(e) => {
+                    metrics.failure(&e);
+                    error!(url = %tgt_url, error = %e, "❌ Couldn't sync the profile, retrying later");
+                }
+            }
+            match profile_sync.sync_pins(&my_id, &mapping).await {
+                Ok((0, 0)) => {}
+                Ok((pinned, unpinned)) => info!(pinned, unpinned, "📌 Pinned posts synced"),
+                Err(e) => {
+                    metrics.failure(&e);
+                    error!(url = %tgt_url, error = %e, "❌ Couldn't sync pinned posts, retrying later");
                 }
             }
         }
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use bot_support::store;
+use chrono::{DateTime, Duration, Utc};
+use mastodon_client::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::clean_html;
+
+/// Local database of source status ID -> target status ID for everything we've mirrored.
+const MAPPING_FILE: &str = "mapping.json";
+/// Posts handed to the target with `scheduled_at` whose target status isn't known yet.
+const SCHEDULED_FILE: &str = "scheduled.json";
+
+/// How far a published status's `created_at` may be from its scheduled time to count as a match.
+const MATCH_WINDOW_MINUTES: i64 = 10;
+/// Stop looking for a scheduled post's status this long after it was due.
+const GIVE_UP_AFTER_DAYS: i64 = 2;
+
+/// A post the target will publish by itself at `due`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledPost {
+    /// ID under `/api/v1/scheduled_statuses` on the target
+    pub scheduled_id: String,
+    pub due: DateTime<Utc>,
+    /// Posted text, to tell apart statuses published around the same time
+    pub text: String,
+}
+
+/// Loads the source→target mapping from local storage.
+pub fn load_mapping() -> Result<HashMap<String, String>> {
+    load(MAPPING_FILE)
+}
+
+/// Persists the source→target mapping.
+pub fn save_mapping(mapping: &HashMap<String, String>) -> Result<()> {
+    save(MAPPING_FILE, mapping)
+}
+
+/// Loads the scheduled posts still waiting for their target ID, by source status ID.
+pub fn load_scheduled() -> Result<HashMap<String, ScheduledPost>> {
+    load(SCHEDULED_FILE)
+}
+
+pub fn save_scheduled(scheduled: &HashMap<String, ScheduledPost>) -> Result<()> {
+    save(SCHEDULED_FILE, scheduled)
+}
+
+/// Adds the target status of every scheduled post the target has published since to `mapping`.
+/// Returns how many were resolved; entries that never show up are dropped after a while.
+pub async fn resolve_scheduled(
+    tgt: &Client,
+    scheduled: &mut HashMap<String, ScheduledPost>,
+    mapping: &mut HashMap<String, String>,
+) -> Result<usize> {
+    let now = Utc::now();
+    let mut published = Vec::new();
+    for (source_id, post) in scheduled.iter().filter(|(_, p)| p.due <= now) {
+        // A scheduled status disappears from the list once it's published
+        match tgt.get(&format!("/api/v1/scheduled_statuses/{}", post.scheduled_id)).await {
+            Ok(_) => continue,
+            Err(e) if mastodon_client::is_gone(&e) => published.push(source_id.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+    if published.is_empty() {
+        return Ok(0);
+    }
+
+    let tgt_id = tgt.verify_credentials().await?.id;
+    let recent = tgt.account_statuses(&tgt_id, &[("limit", "40")]).await?;
+    let mut resolved = 0;
+    for source_id in published {
+        let post = &scheduled[&source_id];
+        let taken: Vec<&String> = mapping.values().collect();
+        // The status published closest to the scheduled time, preferring one with the same text
+        let found = recent.iter()
+            .filter(|s| !taken.contains(&&s.id))
+            .filter_map(|s| {
+                let created = DateTime::parse_from_rfc3339(&s.created_at).ok()?.with_timezone(&Utc);
+                let offset = (created - post.due).num_seconds().abs();
+                (offset <= MATCH_WINDOW_MINUTES * 60).then_some((clean_html(&s.content) != post.text, offset, s.id.clone()))
+            })
+            .min();
+        match found {
+            Some((_, _, target_id)) => {
+                mapping.insert(source_id.clone(), target_id);
+                scheduled.remove(&source_id);
+                resolved += 1;
+            }
+            None if post.due + Duration::days(GIVE_UP_AFTER_DAYS) < now => {
+                scheduled.remove(&source_id);
+            }
+            None => {}
+        }
+    }
+    Ok(resolved)
+}
+
+/// Reads a state file, starting empty if there is none. An unreadable file is an error, since
+/// losing the mapping would break pin alignment for everything mirrored so far.
+fn load<T: serde::de::DeserializeOwned + Default>(file: &str) -> Result<T> {
+    let value = store::load(file).with_context(|| format!("Couldn't load {}; fix or remove it to start over", file))?;
+    Ok(value.unwrap_or_default())
+}
+
+/// Writes a state file, replacing the old one atomically.
+fn save<T: Serialize>(file: &str, value: &T) -> Result<()> {
+    store::save(file, value).with_context(|| format!("Couldn't save {}", file))
+}
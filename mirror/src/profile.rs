@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use bot_support::store;
+use mastodon_client::{multipart, Client};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::clean_html;
+
+/// What was last pushed to the target, kept next to mapping.json so a restart doesn't
+/// re-upload an unchanged profile.
+const PROFILE_FILE: &str = "profile.json";
+
+/// Snapshot of the last source profile pushed to the target.
+#[derive(Serialize, Deserialize, Default)]
+struct Pushed {
+    /// Display name, bio and fields
+    text: Option<String>,
+    avatar: Option<String>,
+    header: Option<String>,
+}
+
+/// Copies the source profile and pinned posts onto the target account.
+pub struct ProfileSync {
+    src: Client,
+    tgt: Client,
+    pushed: Pushed,
+}
+
+impl ProfileSync {
+    pub fn new(src: Client, tgt: Client) -> Result<Self> {
+        let pushed = store::load(PROFILE_FILE)
+            .with_context(|| format!("Couldn't load {}; fix or remove it to start over", PROFILE_FILE))?
+            .unwrap_or_default();
+        Ok(ProfileSync { src, tgt, pushed })
+    }
+
+    /// Pushes display name, bio, fields, avatar and header to the target if the source changed.
+    pub async fn sync_profile(&mut self) -> Result<bool> {
+        let me = self.src.verify_credentials().await?;
+
+        let display_name = me.display_name.clone();
+        // `source` carries the plain-text bio and fields; fall back to the rendered HTML ones
+        let note = me.source.as_ref().map(|s| s.note.clone())
+            .unwrap_or_else(|| clean_html(&me.note));
+        let fields: Vec<(String, String)> = match &me.source {
+            Some(source) if !source.fields.is_empty() => source.fields.iter().map(|f| (f.name.clone(), f.value.clone())).collect(),
+            _ => me.fields.iter().map(|f| (f.name.clone(), clean_html(&f.value))).collect(),
+        };
+        let avatar = me.avatar.clone();
+        let header = me.header.clone();
+
+        let text = serde_json::json!([display_name, note, fields]).to_string();
+        let avatar_changed = self.pushed.avatar.as_deref() != Some(avatar.as_str());
+        let header_changed = self.pushed.header.as_deref() != Some(header.as_str());
+        if self.pushed.text.as_deref() == Some(text.as_str()) && !avatar_changed && !header_changed {
+            return Ok(false);
+        }
+
+        // Fields only on the target are cleared by sending them empty
+        let target_fields = self.tgt.verify_credentials().await?.fields.len();
+        let mut form = multipart::Form::new()
+            .text("display_name", display_name)
+            .text("note", note);
+        for i in 0..fields.len().max(target_fields) {
+            let (name, value) = fields.get(i).cloned().unwrap_or_default();
+            form = form
+                .text(format!("fields_attributes[{}][name]", i), name)
+                .text(format!("fields_attributes[{}][value]", i), value);
+        }
+        for (field, url, changed) in [("avatar", &avatar, avatar_changed), ("header", &header, header_changed)] {
+            // Instances serve a placeholder image path when no avatar/header is set
+            if !changed || url.is_empty() || url.contains("/missing.png") {
+                continue;
+            }
+            let bytes = self.src.download(url).await?;
+            let file_name = url.split('/').next_back().unwrap_or("image.png").to_string();
+            let part = multipart::Part::bytes(bytes)
+                .file_name(file_name)
+                .mime_str("application/octet-stream")?;
+            form = form.part(field, part);
+        }
+
+        self.tgt.update_credentials(form).await?;
+
+        self.pushed = Pushed { text: Some(text), avatar: Some(avatar), header: Some(header) };
+        store::save(PROFILE_FILE, &self.pushed).with_context(|| format!("Couldn't save {}", PROFILE_FILE))?;
+        Ok(true)
+    }
+
+    /// Pins/unpins target statuses so they match the source's pins. Returns (pinned, unpinned) counts.
+    pub async fn sync_pins(&self, src_id: &str, mapping: &HashMap<String, String>) -> Result<(usize, usize)> {
+        let tgt_id = self.tgt.verify_credentials().await?.id;
+
+        let src_pins = pinned_ids(&self.src, src_id).await?;
+        let tgt_pins = pinned_ids(&self.tgt, &tgt_id).await?;
+
+        // Source pins we never mirrored (older than the bot, or skipped) have no target to pin
+        let wanted: HashSet<String> = src_pins.iter().filter_map(|id| mapping.get(id).cloned()).collect();
+        // Only touch target pins that belong to the mirror, so manual pins on the target survive
+        let mirrored: HashSet<&String> = mapping.values().collect();
+
+        let mut pinned = 0;
+        let mut unpinned = 0;
+        for id in wanted.difference(&tgt_pins) {
+            self.tgt.pin(id).await?;
+            pinned += 1;
+        }
+        for id in tgt_pins.difference(&wanted).filter(|id| mirrored.contains(id)) {
+            self.tgt.unpin(id).await?;
+            unpinned += 1;
+        }
+        Ok((pinned, unpinned))
+    }
+}
+
+/// Fetches the IDs of an account's pinned statuses.
+async fn pinned_ids(client: &Client, account_id: &str) -> Result<HashSet<String>> {
+    let statuses = client.account_statuses(account_id, &[("pinned", "true")]).await?;
+    Ok(statuses.into_iter().map(|s| s.id).collect())
+}
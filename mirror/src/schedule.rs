@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::env;
+use bot_support::store;
+
+use crate::Media;
+
+/// Local queue filename for posts that are waiting for their publish time.
+const QUEUE_FILE: &str = "queue.json";
+
+/// Mastodon rejects `scheduled_at` values closer than 5 minutes to now.
+pub const MIN_SCHEDULE_LEAD_MINUTES: i64 = 5;
+/// Extra lead on top of the minimum, for clock skew and the time the request takes.
+const SCHEDULE_SAFETY_MARGIN_MINUTES: i64 = 1;
+/// A post the target keeps rejecting is dropped after this many tries.
+pub const MAX_POST_ATTEMPTS: u32 = 5;
+
+/// How long to wait before trying a rejected post again: 2, 4, 8, 16… minutes.
+pub fn retry_delay(attempts: u32) -> Duration {
+    Duration::minutes(1 << attempts.min(10))
+}
+
+/// True if a post due at `due` is still far enough ahead to be sent with `scheduled_at`.
+pub fn can_schedule(due: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    due - now >= Duration::minutes(MIN_SCHEDULE_LEAD_MINUTES + SCHEDULE_SAFETY_MARGIN_MINUTES)
+}
+
+/// Posting rules: a fixed delay plus quiet hours in the target audience's timezone.
+pub struct Schedule {
+    pub delay: Duration,
+    pub spacing: Duration,
+    pub use_scheduled_at: bool,
+    quiet_hours: Vec<(NaiveTime, NaiveTime)>,
+    tz: Tz,
+}
+
+impl Schedule {
+    /// Reads MIRROR_DELAY_MINUTES, QUIET_HOURS, TARGET_TIMEZONE, POST_SPACING_SECS and USE_SCHEDULED_AT.
+    pub fn from_env() -> Result<Self> {
+        let delay = env::var("MIRROR_DELAY_MINUTES").unwrap_or_else(|_| "0".into())
+            .parse::<i64>().context("MIRROR_DELAY_MINUTES must be a number of minutes")?;
+        let spacing = env::var("POST_SPACING_SECS").unwrap_or_else(|_| "10".into())
+            .parse::<i64>().context("POST_SPACING_SECS must be a number of seconds")?;
+        let tz = env::var("TARGET_TIMEZONE").unwrap_or_else(|_| "UTC".into())
+            .parse::<Tz>().map_err(|e| anyhow::anyhow!("Invalid TARGET_TIMEZONE: {}", e))?;
+        let use_scheduled_at = env::var("USE_SCHEDULED_AT").map(|v| v != "false").unwrap_or(true);
+
+        let mut quiet_hours = Vec::new();
+        for range in env::var("QUIET_HOURS").unwrap_or_default().split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (start, end) = range.split_once('-')
+                .with_context(|| format!("QUIET_HOURS range '{}' must look like 23:00-07:00", range))?;
+            let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").with_context(|| format!("Bad start time in '{}'", range))?;
+            let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").with_context(|| format!("Bad end time in '{}'", range))?;
+            quiet_hours.push((start, end));
+        }
+
+        Ok(Schedule {
+            delay: Duration::minutes(delay.max(0)),
+            spacing: Duration::seconds(spacing.max(0)),
+            use_scheduled_at,
+            quiet_hours,
+            tz,
+        })
+    }
+
+    /// True when posts can go out right away (no delay and no quiet hours configured).
+    pub fn is_immediate(&self) -> bool {
+        self.delay.is_zero() && self.quiet_hours.is_empty()
+    }
+
+    /// Returns the earliest moment at or after `at` that falls outside every quiet range.
+    pub fn next_slot(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let mut slot = at;
+        // Every pass either leaves the slot untouched or pushes it to the end of a range,
+        // so a handful of passes is enough even for overlapping ranges.
+        for _ in 0..=self.quiet_hours.len() * 2 {
+            let local = slot.with_timezone(&self.tz);
+            let time = local.time();
+            let Some(&(start, end)) = self.quiet_hours.iter().find(|(s, e)| in_range(time, *s, *e)) else {
+                return slot;
+            };
+            let mut end_date = local.date_naive();
+            if start > end && time >= start {
+                end_date = end_date.succ_opt().unwrap_or(end_date);
+            }
+            let naive_end = end_date.and_time(end);
+            // A DST gap can make the end time nonexistent locally; the next hour always exists.
+            slot = self.tz.from_local_datetime(&naive_end).earliest()
+                .or_else(|| self.tz.from_local_datetime(&(naive_end + Duration::hours(1))).earliest())
+                .map(|d| d.with_timezone(&Utc))
+                .unwrap_or(slot + Duration::hours(1));
+        }
+        slot
+    }
+}
+
+/// True if `t` is inside [start, end), treating start > end as a range wrapping past midnight.
+fn in_range(t: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        t >= start && t < end
+    } else {
+        t >= start || t < end
+    }
+}
+
+/// A source post waiting in the local queue until its publish time.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueuedPost {
+    pub source_id: String,
+    pub text: String,
+    /// Content warning to keep the text behind, if the source post had one.
+    #[serde(default)]
+    pub spoiler_text: String,
+    pub media: Vec<Media>,
+    pub due: DateTime<Utc>,
+    /// URI of a boosted post to reblog on the target instead of posting `text`.
+    #[serde(default)]
+    pub reblog_of: Option<String>,
+    /// Target media IDs already uploaded for this post, reused when it is handed over again.
+    #[serde(default)]
+    pub uploaded: Option<Vec<String>>,
+    /// The target refused to schedule this post, so it goes out from the local queue when due.
+    #[serde(default)]
+    pub hold_locally: bool,
+    /// How often the target has rejected this post so far
+    #[serde(default)]
+    pub attempts: u32,
+    /// Not before this time, after the target rejected it
+    #[serde(default)]
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+/// Loads the pending queue from local storage. An unreadable file is an error rather than
+/// an empty queue, which would silently drop every delayed post.
+pub fn load_queue() -> Result<Vec<QueuedPost>> {
+    let queue = store::load(QUEUE_FILE).with_context(|| format!("Couldn't load {}; fix or remove it to start over", QUEUE_FILE))?;
+    Ok(queue.unwrap_or_default())
+}
+
+/// Persists the pending queue so queued posts survive restarts, replacing the file atomically.
+pub fn save_queue(queue: &[QueuedPost]) -> Result<()> {
+    store::save(QUEUE_FILE, queue)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(quiet_hours: &[(&str, &str)], tz: Tz) -> Schedule {
+        let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+        Schedule {
+            delay: Duration::zero(),
+            spacing: Duration::zero(),
+            use_scheduled_at: true,
+            quiet_hours: quiet_hours.iter().map(|(s, e)| (time(s), time(e))).collect(),
+            tz,
+        }
+    }
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn outside_quiet_hours_is_unchanged() {
+        let s = schedule(&[("23:00", "07:00")], Tz::UTC);
+        assert_eq!(s.next_slot(utc("2024-05-01T12:00:00Z")), utc("2024-05-01T12:00:00Z"));
+        // The end of a range is already allowed
+        assert_eq!(s.next_slot(utc("2024-05-01T07:00:00Z")), utc("2024-05-01T07:00:00Z"));
+    }
+
+    #[test]
+    fn range_past_midnight_ends_the_next_morning() {
+        let s = schedule(&[("23:00", "07:00")], Tz::UTC);
+        assert_eq!(s.next_slot(utc("2024-05-01T23:30:00Z")), utc("2024-05-02T07:00:00Z"));
+        assert_eq!(s.next_slot(utc("2024-05-02T03:00:00Z")), utc("2024-05-02T07:00:00Z"));
+    }
+
+    #[test]
+    fn adjacent_ranges_are_skipped_together() {
+        let s = schedule(&[("12:00", "13:00"), ("13:00", "14:00")], Tz::UTC);
+        assert_eq!(s.next_slot(utc("2024-05-01T12:15:00Z")), utc("2024-05-01T14:00:00Z"));
+    }
+
+    #[test]
+    fn quiet_hours_follow_the_target_timezone() {
+        // 22:00 UTC is midnight in Berlin during summer time
+        let s = schedule(&[("23:00", "07:00")], Tz::Europe__Berlin);
+        assert_eq!(s.next_slot(utc("2024-07-01T22:00:00Z")), utc("2024-07-02T05:00:00Z"));
+    }
+
+    #[test]
+    fn rejected_posts_back_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::minutes(2));
+        assert_eq!(retry_delay(4), Duration::minutes(16));
+        // Capped so a huge counter from a hand-edited queue can't overflow
+        assert_eq!(retry_delay(40), Duration::minutes(1024));
+    }
+
+    #[test]
+    fn scheduling_needs_the_minimum_lead_plus_margin() {
+        let now = utc("2024-05-01T12:00:00Z");
+        assert!(can_schedule(now + Duration::minutes(6), now));
+        assert!(!can_schedule(now + Duration::minutes(5), now));
+    }
+
+    #[test]
+    fn end_inside_a_dst_gap_moves_an_hour_later() {
+        // 02:30 doesn't exist in Berlin on 2024-03-31; clocks jump from 02:00 to 03:00
+        let s = schedule(&[("01:00", "02:30")], Tz::Europe__Berlin);
+        assert_eq!(s.next_slot(utc("2024-03-31T00:15:00Z")), utc("2024-03-31T01:30:00Z"));
+    }
+}
@@ -3,15 +3,13 @@ use dotenv::dotenv;
 use lettre::message::header::ContentType;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
-use regex::Regex;
-use reqwest::blocking::Client;
-use reqwest::header::LINK;
-use serde::{Deserialize, Serialize};
+use mastodon_client::blocking::Client;
 use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
+use tracing::{error, info, warn};
 
 /// Local database filename for persisting follower state between executions.
 const FOLLOWERS_FILE: &str = "followers.json";
@@ -40,20 +38,6 @@ impl Config {
     }
 }
 
-/// Represents a Mastodon account entity as returned by the API.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Account {
-    id: String,
-    username: String,
-    acct: String, // Fully qualified or local account handle
-}
-
-/// Response structure for the /api/v1/accounts/verify_credentials endpoint.
-#[derive(Deserialize)]
-struct VerifyCredentials {
-    id: String,
-}
-
 /// Dispatches a plain-text email notification via an SMTP relay.
 fn send_email(config: &Config, subject: &str, body: &str) -> Result<()> {
     let email = Message::builder()
@@ -71,69 +55,33 @@ fn send_email(config: &Config, subject: &str, body: &str) -> Result<()> {
         .build();
 
     match mailer.send(&email) {
-        Ok(_) => println!("[INFO] Email notification sent successfully."),
-        Err(e) => eprintln!("[ERROR] SMTP failure: {:?}", e),
+        Ok(_) => info!("Email notification sent successfully."),
+        Err(e) => error!(server = %config.smtp_server, error = ?e, "SMTP failure"),
     }
 
     Ok(())
 }
 
-/// Retrieves all followers for the authenticated user, handling API pagination.
+/// Retrieves all followers for the authenticated user (the client follows the API pagination).
 fn get_followers(client: &Client, config: &Config) -> Result<HashMap<String, String>> {
     // 1. Identify current user ID via credential verification
-    let verify_url = format!("{}/api/v1/accounts/verify_credentials", config.instance_url);
-    let user_data: VerifyCredentials = client
-        .get(&verify_url)
-        .header("Authorization", format!("Bearer {}", config.access_token))
-        .send()
-        .context("Failed to verify Mastodon credentials")?
-        .json()?;
-
-    let user_id = user_data.id;
-    let mut followers_url = Some(format!("{}/api/v1/accounts/{}/followers", config.instance_url, user_id));
-    let mut followers_map = HashMap::new();
+    let user_id = client.verify_credentials()
+        .with_context(|| format!("Failed to verify Mastodon credentials at {}", config.instance_url))?
+        .id;
+
+    // 2. Fetch every page of followers
+    let accounts = client.followers(&user_id).context("Failed to retrieve followers")?;
 
-    // Regex for parsing RFC 5988 Link headers used by Mastodon for pagination
-    let re = Regex::new(r#"<([^>]+)>;\s*rel="next""#).unwrap();
-
-    // Determine the local instance domain for handle normalization
-    let local_domain = config.instance_url
-        .replace("https://", "")
-        .replace("http://", "")
-        .trim_end_matches('/')
-        .to_string();
-
-    // 2. Iterate through paginated results
-
-    while let Some(url) = followers_url {
-        let response = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", config.access_token))
-            .send()
-            .context("Failed to retrieve followers page")?;
-
-        // Parse 'Link' header for the next page URL
-        let next_url = if let Some(link_header) = response.headers().get(LINK) {
-            let link_str = link_header.to_str().unwrap_or("");
-            re.captures(link_str).map(|cap| cap[1].to_string())
+    let mut followers_map = HashMap::new();
+    for acc in accounts {
+        // Normalize handles: ensure local users have the @domain suffix for consistency
+        let full_handle = if acc.acct.contains('@') {
+            acc.acct
         } else {
-            None
+            format!("{}@{}", acc.acct, client.domain())
         };
 
-        let accounts: Vec<Account> = response.json().context("Failed to parse follower JSON payload")?;
-
-        for acc in accounts {
-            // Normalize handles: ensure local users have the @domain suffix for consistency
-            let full_handle = if acc.acct.contains('@') {
-                acc.acct
-            } else {
-                format!("{}@{}", acc.acct, local_domain)
-            };
-
-            followers_map.insert(acc.id, full_handle);
-        }
-
-        followers_url = next_url;
+        followers_map.insert(acc.id, full_handle);
     }
 
     Ok(followers_map)
@@ -146,7 +94,10 @@ fn load_previous_followers() -> HashMap<String, String> {
     }
     let file = File::open(FOLLOWERS_FILE).expect("Failed to open local state file");
     let reader = BufReader::new(file);
-    serde_json::from_reader(reader).unwrap_or_default()
+    serde_json::from_reader(reader).unwrap_or_else(|e| {
+        warn!(file = FOLLOWERS_FILE, error = %e, "Corrupt follower state, starting fresh");
+        HashMap::new()
+    })
 }
 
 /// Persists the current follower state to local storage.
@@ -158,14 +109,13 @@ fn save_followers(followers: &HashMap<String, String>) -> Result<()> {
 
 /// Orchestrates the unfollower check: loads state, fetches current data, diffs them, and notifies if necessary.
 fn check_unfollowers() -> Result<()> {
-    dotenv().ok();
     let config = Config::from_env().context("Configuration error")?;
-    let client = Client::new();
+    let client = Client::new(&config.instance_url, &config.access_token)?;
 
-    println!("[INFO] Loading cached follower state...");
+    info!(file = FOLLOWERS_FILE, "Loading cached follower state...");
     let old_followers = load_previous_followers();
 
-    println!("[INFO] Fetching current followers from the API...");
+    info!(instance = %config.instance_url, "Fetching current followers from the API...");
     let new_followers = get_followers(&client, &config)?;
 
     // Identify users present in the old list but absent in the new list
@@ -186,10 +136,10 @@ fn check_unfollowers() -> Result<()> {
         }
 
         // Now we can safely access unfollowers.len() because we only borrowed the data in the loop above.
-        println!("[ALERT] Detected {} unfollowers.", unfollowers.len());
+        warn!(count = unfollowers.len(), "Detected unfollowers");
         send_email(&config, "Mastodon Unfollower Alert", &message)?;
     } else {
-        println!("[INFO] No new unfollowers detected.");
+        info!(followers = new_followers.len(), "No new unfollowers detected.");
     }
 
     // Update the local state with the latest follower list
@@ -198,7 +148,10 @@ fn check_unfollowers() -> Result<()> {
 }
 
 fn main() {
+    dotenv().ok();
+    bot_support::logging::init();
+
     if let Err(e) = check_unfollowers() {
-        eprintln!("[FATAL] Application error: {:#}", e);
+        error!(error = format!("{:#}", e), "Application error");
     }
 }
@@ -2,12 +2,11 @@ use std::collections::HashSet;
 use std::env;
 use std::fs::{File, OpenOptions};
 use std::io::{BufReader, BufWriter};
-use std::thread;
-use std::time::Duration;
 use dotenv::dotenv;
+use mastodon_client::NewStatus;
 use reqwest::blocking::Client;
-use reqwest::blocking::multipart;
 use serde::Deserialize;
+use tracing::{error, info, warn};
 
 // --- Unsplash Structs ---
 
@@ -50,13 +49,6 @@ struct UnsplashTag {
     title: String,
 }
 
-// --- Mastodon Structs ---
-
-#[derive(Deserialize, Debug)]
-struct MastoMedia {
-    id: String,
-}
-
 // --- History Logic ---
 
 const HISTORY_FILE: &str = "history.json";
@@ -81,31 +73,41 @@ fn save_history(history: &HashSet<String>) {
     serde_json::to_writer_pretty(writer, history).expect("Failed to save history");
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
     dotenv().ok();
+    bot_support::logging::init();
 
+    if let Err(e) = run() {
+        error!(error = %e, "Application error");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
     let unsplash_key = env::var("UNSPLASH_ACCESS_KEY").expect("Missing UNSPLASH_ACCESS_KEY");
     let masto_token = env::var("MASTODON_ACCESS_TOKEN").expect("Missing MASTODON_ACCESS_TOKEN");
     let masto_url = env::var("MASTODON_INSTANCE_URL").expect("Missing MASTODON_INSTANCE_URL");
 
     let client = Client::new();
+    let mastodon = mastodon_client::blocking::Client::new(&masto_url, &masto_token)?;
     let mut history = load_history();
 
     // 1. Search for photos (Topic: Wallpapers, Order: Popular)
     let url = "https://api.unsplash.com/topics/wallpapers/photos?orientation=landscape&order_by=popular&page=1&per_page=30";
 
-    println!("--> [1/4] Searching Unsplash...");
+    info!(step = "1/4", "Searching Unsplash...");
     let resp = client.get(url)
         .header("Authorization", format!("Client-ID {}", unsplash_key))
         .send()?
+        .error_for_status()?
         .json::<Vec<UnsplashPhoto>>()?;
 
     // Select the first photo that is not in the history
     let selected_photo = resp.iter().find(|p| !history.contains(&p.id));
 
     if let Some(photo) = selected_photo {
-        println!("--> Selected Photo: {}", photo.id);
+        info!(photo_id = %photo.id, "Selected photo");
 
         // --- Build ALT TEXT ---
 
@@ -155,65 +157,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // --- Download and Upload ---
 
-        println!("--> [2/4] Downloading image...");
-        let img_bytes = client.get(&photo.urls.regular).send()?.bytes()?;
-
-        println!("--> [3/4] Uploading to Mastodon...");
-        let upload_url = format!("{}/api/v2/media", masto_url);
-
-        let part = multipart::Part::bytes(img_bytes.to_vec())
-            .file_name("wallpaper.jpg")
-            .mime_str("image/jpeg")?;
+        info!(step = "2/4", url = %photo.urls.regular, "Downloading image...");
+        let img_bytes = client.get(&photo.urls.regular).send()?.error_for_status()?.bytes()?;
 
-        // Important: the 'description' field here sets the Alt Text
-        let form = multipart::Form::new()
-            .part("file", part)
-            .text("description", safe_alt_text);
+        info!(step = "3/4", "Uploading to Mastodon...");
 
-        let media_resp = client.post(&upload_url)
-            .header("Authorization", format!("Bearer {}", masto_token))
-            .multipart(form)
-            .send()?;
-
-        if !media_resp.status().is_success() {
-            println!("Error Uploading Media: {:?}", media_resp.text());
-            return Ok(());
-        }
-
-        let media_json: MastoMedia = media_resp.json()?;
-        println!("    Media ID: {}", media_json.id);
-
-        // Give the server some time to process the uploaded image
-        thread::sleep(Duration::from_secs(3));
+        // Important: the description sets the Alt Text. The upload returns once the server has processed the image.
+        let media = match mastodon.upload_media(img_bytes.to_vec(), "wallpaper.jpg", "image/jpeg", Some(&safe_alt_text)) {
+            Ok(media) => media,
+            Err(e) => {
+                error!(error = %e, "Error uploading media");
+                return Ok(());
+            }
+        };
+        info!(media_id = %media.id, "Media uploaded");
 
         // --- Posting ---
 
-        println!("--> [4/4] Posting clean status...");
-
-        let post_url = format!("{}/api/v1/statuses", masto_url);
+        info!(step = "4/4", "Posting clean status...");
 
         // Send an empty status body, but with attached media_ids
-        let post_params = serde_json::json!({
-            "status": "",
-            "media_ids": [media_json.id],
-            "visibility": "public"
-        });
-
-        let post_resp = client.post(&post_url)
-            .header("Authorization", format!("Bearer {}", masto_token))
-            .json(&post_params)
-            .send()?;
-
-        if post_resp.status().is_success() {
-            println!("--> SUCCESS!");
-            history.insert(photo.id.clone());
-            save_history(&history);
-        } else {
-            println!("Error Posting Status: {:?}", post_resp.text());
+        let post_params = NewStatus::new("").media(vec![media.id]).visibility("public");
+
+        match mastodon.post_status(&post_params) {
+            Ok(_) => {
+                info!(photo_id = %photo.id, "SUCCESS!");
+                history.insert(photo.id.clone());
+                save_history(&history);
+            }
+            Err(e) => error!(error = %e, "Error posting status"),
         }
 
     } else {
-        println!("No new photos found on the first page.");
+        warn!("No new photos found on the first page.");
     }
 
     Ok(())
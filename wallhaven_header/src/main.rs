@@ -1,7 +1,9 @@
 use std::env;
 use dotenv::dotenv;
-use reqwest::blocking::{Client, multipart};
+use mastodon_client::multipart;
+use reqwest::blocking::Client;
 use serde::Deserialize;
+use tracing::{error, info, warn};
 
 /// Represents the top-level response structure from the Wallhaven API.
 #[derive(Deserialize, Debug)]
@@ -17,10 +19,18 @@ struct WallhavenPhoto {
     dimension_y: u32,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
     // Initialize environment variables from .env file
     dotenv().ok();
+    bot_support::logging::init();
 
+    if let Err(e) = run() {
+        error!(error = %e, "Application error");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let wallhaven_key = env::var("WALLHAVEN_API_KEY")
         .expect("Environment variable WALLHAVEN_API_KEY is required");
     let masto_token = env::var("MASTODON_ACCESS_TOKEN")
@@ -32,6 +42,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = Client::builder()
         .user_agent("WallhavenHeaderAutomation/1.0 (Rust; Cross-Compiled)")
         .build()?;
+    let mastodon = mastodon_client::blocking::Client::new(&masto_url, &masto_token)?;
 
     // Step 1: Query Wallhaven API for high-resolution cyberpunk imagery
     // Constraints:
@@ -44,22 +55,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         wallhaven_key
     );
 
-    println!("[INFO] Querying Wallhaven API...");
-    let search_resp = client.get(search_url).send()?.json::<WallhavenResponse>()?;
+    info!("Querying Wallhaven API...");
+    // The search URL carries the API key, so strip it from any error before it reaches the logs
+    let search_resp = client.get(search_url).send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.json::<WallhavenResponse>())
+        .map_err(|e| e.without_url())?;
 
     if let Some(photo) = search_resp.data.first() {
-        println!("[INFO] Target image identified: {} ({}x{})", photo.path, photo.dimension_x, photo.dimension_y);
+        info!(url = %photo.path, width = photo.dimension_x, height = photo.dimension_y, "Target image identified");
 
         // Step 2: Download the selected resource into memory
-        println!("[INFO] Fetching image bytes...");
-        let img_bytes = client.get(&photo.path).send()?.bytes()?;
+        info!("Fetching image bytes...");
+        let img_bytes = client.get(&photo.path).send()?.error_for_status()?.bytes()?;
 
         // Step 3: Update Mastodon account credentials
         // Endpoint: PATCH /api/v1/accounts/update_credentials
         // Documentation: https://docs.joinmastodon.org/methods/accounts/#update_credentials
-        println!("[INFO] Updating Mastodon profile header...");
-        let update_url = format!("{}/api/v1/accounts/update_credentials", masto_url);
-
+        info!("Updating Mastodon profile header...");
         let image_part = multipart::Part::bytes(img_bytes.to_vec())
             .file_name("header.jpg")
             .mime_str("image/jpeg")?;
@@ -67,19 +80,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let form = multipart::Form::new()
             .part("header", image_part);
 
-        let masto_resp = client.patch(&update_url)
-            .header("Authorization", format!("Bearer {}", masto_token))
-            .multipart(form)
-            .send()?;
-
-        if masto_resp.status().is_success() {
-            println!("[SUCCESS] Profile header successfully updated.");
-        } else {
-            let error_text = masto_resp.text()?;
-            eprintln!("[ERROR] Mastodon API rejection: {}", error_text);
+        match mastodon.update_credentials(form) {
+            Ok(_) => info!("Profile header successfully updated."),
+            Err(e) => error!(error = %e, "Mastodon API rejection"),
         }
     } else {
-        println!("[WARN] No images matching the specified criteria were found.");
+        warn!("No images matching the specified criteria were found.");
     }
 
     Ok(())